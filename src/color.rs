@@ -0,0 +1,446 @@
+//! SVG colour values, and the tikz colour declarations they turn into
+
+use std::fmt::Display;
+
+use crate::{palette, Options, Recolor, Variant};
+
+/// An sRGB colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    /// The colour as `RRGGBB`, as used by xcolor's `HTML` model
+    pub fn hex(&self) -> String {
+        format!("{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+
+    /// Parses a CSS colour value into the colour and its alpha: hex
+    /// (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`), `rgb()`/`rgba()`, or a
+    /// named colour
+    pub fn parse(s: &str) -> Option<(Color, f32)> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+        let lower = s.to_ascii_lowercase();
+        if let Some(args) = lower
+            .strip_prefix("rgba(")
+            .or_else(|| lower.strip_prefix("rgb("))
+        {
+            return parse_rgb(args.strip_suffix(')')?);
+        }
+        if lower == "transparent" {
+            return Some((Color(0, 0, 0), 0.0));
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == lower)
+            .map(|&(_, color)| (color, 1.0))
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<(Color, f32)> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<u8> = match hex.len() {
+        // each digit of the short forms stands for a doubled digit
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8 * 17))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    let alpha = digits.get(3).map_or(1.0, |&a| a as f32 / 255.0);
+    Some((Color(digits[0], digits[1], digits[2]), alpha))
+}
+
+/// Parses the arguments of `rgb()`/`rgba()`, in either the legacy comma
+/// separated syntax or the space separated one with a `/` before the alpha
+fn parse_rgb(args: &str) -> Option<(Color, f32)> {
+    let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|a| !a.is_empty())
+        .collect();
+    let component = |s: &str| -> Option<u8> {
+        let value = match s.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? * 2.55,
+            None => s.parse::<f32>().ok()?,
+        };
+        Some(value.round().clamp(0.0, 255.0) as u8)
+    };
+    let alpha = |s: &str| -> Option<f32> {
+        let value = match s.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => s.parse::<f32>().ok()?,
+        };
+        Some(value.clamp(0.0, 1.0))
+    };
+    match args.as_slice() {
+        [r, g, b] => Some((Color(component(r)?, component(g)?, component(b)?), 1.0)),
+        [r, g, b, a] => Some((
+            Color(component(r)?, component(g)?, component(b)?),
+            alpha(a)?,
+        )),
+        _ => None,
+    }
+}
+
+/// What the inside or outline of a shape is painted with
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Paint {
+    None,
+    /// A colour and its alpha
+    Color(Color, f32),
+    /// Whatever the `color` property is
+    CurrentColor,
+    /// The paint server (gradient, pattern) with the given id, and what to
+    /// use where it can't be
+    Url(String, Box<Paint>),
+}
+
+impl Paint {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s == "none" {
+            Some(Paint::None)
+        } else if s.eq_ignore_ascii_case("currentcolor") {
+            Some(Paint::CurrentColor)
+        } else if let Some(reference) = s.strip_prefix("url(") {
+            let (url, fallback) = reference.split_once(')')?;
+            let fallback = match fallback.trim() {
+                "" => Paint::None,
+                fallback => Paint::parse(fallback)?,
+            };
+            let url = url.trim().trim_matches(|c| c == '"' || c == '\'');
+            // only same-document references can be resolved
+            match url.strip_prefix('#') {
+                Some(id) => Some(Paint::Url(id.to_string(), Box::new(fallback))),
+                None => Some(fallback),
+            }
+        } else {
+            Color::parse(s).map(|(color, alpha)| Paint::Color(color, alpha))
+        }
+    }
+}
+
+/// The colours used in a picture, in order of first use, each with the name
+/// it is declared under, along with the style keys that replace some of them
+#[derive(Debug, Default)]
+pub(crate) struct ColorTable {
+    /// Each colour with its name, and whether that is from the palette
+    colors: Vec<(String, Color, bool)>,
+    recolor: Option<Recolor>,
+    /// Colours to replace before anything else, each with its replacement
+    remap: Vec<(Color, Color)>,
+    /// Whether to name colours from the AWS palette
+    palette: bool,
+    /// The icon's category, as its title gives it
+    category: Option<String>,
+    /// The keys used, each with the name of the colour it defaults to
+    keys: Vec<(String, String)>,
+}
+
+impl ColorTable {
+    /// A table for the colours of an icon with the given `title`, of the
+    /// `source` variant if that's known
+    pub fn new(options: &Options, title: Option<&str>, source: Option<Variant>) -> Self {
+        ColorTable {
+            recolor: options.recolor.clone(),
+            remap: match (options.variant, source) {
+                (Some(variant), Some(source)) => variant.remap(source, title),
+                _ => vec![],
+            },
+            palette: options.aws_palette,
+            category: title.and_then(palette::category).map(str::to_string),
+            ..ColorTable::default()
+        }
+    }
+
+    fn remapped(&self, color: Color) -> Color {
+        self.remap
+            .iter()
+            .find(|(from, _)| *from == color)
+            .map_or(color, |&(_, to)| to)
+    }
+
+    /// The name `color` is declared under, declaring it if it's new
+    pub fn name(&mut self, color: Color) -> String {
+        let color = self.remapped(color);
+        if let Some((name, _, _)) = self.colors.iter().find(|(_, c, _)| *c == color) {
+            return name.clone();
+        }
+        let from_palette = match self.palette {
+            true => palette::name(color, self.category.as_deref()),
+            false => None,
+        };
+        let is_palette = from_palette.is_some();
+        let name = from_palette.unwrap_or_else(|| format!("svg{}", color.hex()));
+        self.colors.push((name.clone(), color, is_palette));
+        name
+    }
+
+    /// The style key that replaces `color`, if any, declaring it if it's
+    /// new. `current` is whether the colour came from `currentColor`.
+    pub fn key(&mut self, color: Color, current: bool) -> Option<String> {
+        let color = self.remapped(color);
+        let recolor = self.recolor.as_ref()?;
+        let key = match recolor.colors.iter().find(|(c, _)| *c == color) {
+            Some((_, key)) => key.clone(),
+            None if current => recolor.current_color.clone(),
+            None => return None,
+        };
+        if !self.keys.iter().any(|(k, _)| *k == key) {
+            let default = self.name(color);
+            self.keys.push((key.clone(), default));
+        }
+        Some(key)
+    }
+}
+
+impl Display for ColorTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, color, from_palette) in &self.colors {
+            // palette colours the document has defined already are left
+            // alone
+            let command = match from_palette {
+                true => "providecolor",
+                false => "definecolor",
+            };
+            writeln!(f, "\\{}{{{}}}{{HTML}}{{{}}}", command, name, color.hex())?;
+        }
+        // keys the document has set already are left alone, but they
+        // always paint fills when used without a value
+        for (key, default) in &self.keys {
+            writeln!(
+                f,
+                "\\pgfkeysifdefined{{/tikz/{key}/.@cmd}}{{}}\
+                 {{\\tikzset{{{key}/.style={{#1={default}}}}}}}\n\
+                 \\tikzset{{{key}/.default=fill}}",
+                key = key,
+                default = default,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The CSS named colours
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("aliceblue", Color(0xf0, 0xf8, 0xff)),
+    ("antiquewhite", Color(0xfa, 0xeb, 0xd7)),
+    ("aqua", Color(0x00, 0xff, 0xff)),
+    ("aquamarine", Color(0x7f, 0xff, 0xd4)),
+    ("azure", Color(0xf0, 0xff, 0xff)),
+    ("beige", Color(0xf5, 0xf5, 0xdc)),
+    ("bisque", Color(0xff, 0xe4, 0xc4)),
+    ("black", Color(0x00, 0x00, 0x00)),
+    ("blanchedalmond", Color(0xff, 0xeb, 0xcd)),
+    ("blue", Color(0x00, 0x00, 0xff)),
+    ("blueviolet", Color(0x8a, 0x2b, 0xe2)),
+    ("brown", Color(0xa5, 0x2a, 0x2a)),
+    ("burlywood", Color(0xde, 0xb8, 0x87)),
+    ("cadetblue", Color(0x5f, 0x9e, 0xa0)),
+    ("chartreuse", Color(0x7f, 0xff, 0x00)),
+    ("chocolate", Color(0xd2, 0x69, 0x1e)),
+    ("coral", Color(0xff, 0x7f, 0x50)),
+    ("cornflowerblue", Color(0x64, 0x95, 0xed)),
+    ("cornsilk", Color(0xff, 0xf8, 0xdc)),
+    ("crimson", Color(0xdc, 0x14, 0x3c)),
+    ("cyan", Color(0x00, 0xff, 0xff)),
+    ("darkblue", Color(0x00, 0x00, 0x8b)),
+    ("darkcyan", Color(0x00, 0x8b, 0x8b)),
+    ("darkgoldenrod", Color(0xb8, 0x86, 0x0b)),
+    ("darkgray", Color(0xa9, 0xa9, 0xa9)),
+    ("darkgreen", Color(0x00, 0x64, 0x00)),
+    ("darkgrey", Color(0xa9, 0xa9, 0xa9)),
+    ("darkkhaki", Color(0xbd, 0xb7, 0x6b)),
+    ("darkmagenta", Color(0x8b, 0x00, 0x8b)),
+    ("darkolivegreen", Color(0x55, 0x6b, 0x2f)),
+    ("darkorange", Color(0xff, 0x8c, 0x00)),
+    ("darkorchid", Color(0x99, 0x32, 0xcc)),
+    ("darkred", Color(0x8b, 0x00, 0x00)),
+    ("darksalmon", Color(0xe9, 0x96, 0x7a)),
+    ("darkseagreen", Color(0x8f, 0xbc, 0x8f)),
+    ("darkslateblue", Color(0x48, 0x3d, 0x8b)),
+    ("darkslategray", Color(0x2f, 0x4f, 0x4f)),
+    ("darkslategrey", Color(0x2f, 0x4f, 0x4f)),
+    ("darkturquoise", Color(0x00, 0xce, 0xd1)),
+    ("darkviolet", Color(0x94, 0x00, 0xd3)),
+    ("deeppink", Color(0xff, 0x14, 0x93)),
+    ("deepskyblue", Color(0x00, 0xbf, 0xff)),
+    ("dimgray", Color(0x69, 0x69, 0x69)),
+    ("dimgrey", Color(0x69, 0x69, 0x69)),
+    ("dodgerblue", Color(0x1e, 0x90, 0xff)),
+    ("firebrick", Color(0xb2, 0x22, 0x22)),
+    ("floralwhite", Color(0xff, 0xfa, 0xf0)),
+    ("forestgreen", Color(0x22, 0x8b, 0x22)),
+    ("fuchsia", Color(0xff, 0x00, 0xff)),
+    ("gainsboro", Color(0xdc, 0xdc, 0xdc)),
+    ("ghostwhite", Color(0xf8, 0xf8, 0xff)),
+    ("gold", Color(0xff, 0xd7, 0x00)),
+    ("goldenrod", Color(0xda, 0xa5, 0x20)),
+    ("gray", Color(0x80, 0x80, 0x80)),
+    ("green", Color(0x00, 0x80, 0x00)),
+    ("greenyellow", Color(0xad, 0xff, 0x2f)),
+    ("grey", Color(0x80, 0x80, 0x80)),
+    ("honeydew", Color(0xf0, 0xff, 0xf0)),
+    ("hotpink", Color(0xff, 0x69, 0xb4)),
+    ("indianred", Color(0xcd, 0x5c, 0x5c)),
+    ("indigo", Color(0x4b, 0x00, 0x82)),
+    ("ivory", Color(0xff, 0xff, 0xf0)),
+    ("khaki", Color(0xf0, 0xe6, 0x8c)),
+    ("lavender", Color(0xe6, 0xe6, 0xfa)),
+    ("lavenderblush", Color(0xff, 0xf0, 0xf5)),
+    ("lawngreen", Color(0x7c, 0xfc, 0x00)),
+    ("lemonchiffon", Color(0xff, 0xfa, 0xcd)),
+    ("lightblue", Color(0xad, 0xd8, 0xe6)),
+    ("lightcoral", Color(0xf0, 0x80, 0x80)),
+    ("lightcyan", Color(0xe0, 0xff, 0xff)),
+    ("lightgoldenrodyellow", Color(0xfa, 0xfa, 0xd2)),
+    ("lightgray", Color(0xd3, 0xd3, 0xd3)),
+    ("lightgreen", Color(0x90, 0xee, 0x90)),
+    ("lightgrey", Color(0xd3, 0xd3, 0xd3)),
+    ("lightpink", Color(0xff, 0xb6, 0xc1)),
+    ("lightsalmon", Color(0xff, 0xa0, 0x7a)),
+    ("lightseagreen", Color(0x20, 0xb2, 0xaa)),
+    ("lightskyblue", Color(0x87, 0xce, 0xfa)),
+    ("lightslategray", Color(0x77, 0x88, 0x99)),
+    ("lightslategrey", Color(0x77, 0x88, 0x99)),
+    ("lightsteelblue", Color(0xb0, 0xc4, 0xde)),
+    ("lightyellow", Color(0xff, 0xff, 0xe0)),
+    ("lime", Color(0x00, 0xff, 0x00)),
+    ("limegreen", Color(0x32, 0xcd, 0x32)),
+    ("linen", Color(0xfa, 0xf0, 0xe6)),
+    ("magenta", Color(0xff, 0x00, 0xff)),
+    ("maroon", Color(0x80, 0x00, 0x00)),
+    ("mediumaquamarine", Color(0x66, 0xcd, 0xaa)),
+    ("mediumblue", Color(0x00, 0x00, 0xcd)),
+    ("mediumorchid", Color(0xba, 0x55, 0xd3)),
+    ("mediumpurple", Color(0x93, 0x70, 0xdb)),
+    ("mediumseagreen", Color(0x3c, 0xb3, 0x71)),
+    ("mediumslateblue", Color(0x7b, 0x68, 0xee)),
+    ("mediumspringgreen", Color(0x00, 0xfa, 0x9a)),
+    ("mediumturquoise", Color(0x48, 0xd1, 0xcc)),
+    ("mediumvioletred", Color(0xc7, 0x15, 0x85)),
+    ("midnightblue", Color(0x19, 0x19, 0x70)),
+    ("mintcream", Color(0xf5, 0xff, 0xfa)),
+    ("mistyrose", Color(0xff, 0xe4, 0xe1)),
+    ("moccasin", Color(0xff, 0xe4, 0xb5)),
+    ("navajowhite", Color(0xff, 0xde, 0xad)),
+    ("navy", Color(0x00, 0x00, 0x80)),
+    ("oldlace", Color(0xfd, 0xf5, 0xe6)),
+    ("olive", Color(0x80, 0x80, 0x00)),
+    ("olivedrab", Color(0x6b, 0x8e, 0x23)),
+    ("orange", Color(0xff, 0xa5, 0x00)),
+    ("orangered", Color(0xff, 0x45, 0x00)),
+    ("orchid", Color(0xda, 0x70, 0xd6)),
+    ("palegoldenrod", Color(0xee, 0xe8, 0xaa)),
+    ("palegreen", Color(0x98, 0xfb, 0x98)),
+    ("paleturquoise", Color(0xaf, 0xee, 0xee)),
+    ("palevioletred", Color(0xdb, 0x70, 0x93)),
+    ("papayawhip", Color(0xff, 0xef, 0xd5)),
+    ("peachpuff", Color(0xff, 0xda, 0xb9)),
+    ("peru", Color(0xcd, 0x85, 0x3f)),
+    ("pink", Color(0xff, 0xc0, 0xcb)),
+    ("plum", Color(0xdd, 0xa0, 0xdd)),
+    ("powderblue", Color(0xb0, 0xe0, 0xe6)),
+    ("purple", Color(0x80, 0x00, 0x80)),
+    ("rebeccapurple", Color(0x66, 0x33, 0x99)),
+    ("red", Color(0xff, 0x00, 0x00)),
+    ("rosybrown", Color(0xbc, 0x8f, 0x8f)),
+    ("royalblue", Color(0x41, 0x69, 0xe1)),
+    ("saddlebrown", Color(0x8b, 0x45, 0x13)),
+    ("salmon", Color(0xfa, 0x80, 0x72)),
+    ("sandybrown", Color(0xf4, 0xa4, 0x60)),
+    ("seagreen", Color(0x2e, 0x8b, 0x57)),
+    ("seashell", Color(0xff, 0xf5, 0xee)),
+    ("sienna", Color(0xa0, 0x52, 0x2d)),
+    ("silver", Color(0xc0, 0xc0, 0xc0)),
+    ("skyblue", Color(0x87, 0xce, 0xeb)),
+    ("slateblue", Color(0x6a, 0x5a, 0xcd)),
+    ("slategray", Color(0x70, 0x80, 0x90)),
+    ("slategrey", Color(0x70, 0x80, 0x90)),
+    ("snow", Color(0xff, 0xfa, 0xfa)),
+    ("springgreen", Color(0x00, 0xff, 0x7f)),
+    ("steelblue", Color(0x46, 0x82, 0xb4)),
+    ("tan", Color(0xd2, 0xb4, 0x8c)),
+    ("teal", Color(0x00, 0x80, 0x80)),
+    ("thistle", Color(0xd8, 0xbf, 0xd8)),
+    ("tomato", Color(0xff, 0x63, 0x47)),
+    ("turquoise", Color(0x40, 0xe0, 0xd0)),
+    ("violet", Color(0xee, 0x82, 0xee)),
+    ("wheat", Color(0xf5, 0xde, 0xb3)),
+    ("white", Color(0xff, 0xff, 0xff)),
+    ("whitesmoke", Color(0xf5, 0xf5, 0xf5)),
+    ("yellow", Color(0xff, 0xff, 0x00)),
+    ("yellowgreen", Color(0x9a, 0xcd, 0x32)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_colors() {
+        let orange = Color(0xED, 0x71, 0x00);
+        assert_eq!(Color::parse("#ED7100"), Some((orange, 1.0)));
+        assert_eq!(Color::parse("#ed7100"), Some((orange, 1.0)));
+        assert_eq!(Color::parse("#fff"), Some((Color(255, 255, 255), 1.0)));
+        assert_eq!(Color::parse("#ED710080").map(|c| c.0), Some(orange));
+        assert_eq!(Color::parse("rgb(237, 113, 0)"), Some((orange, 1.0)));
+        assert_eq!(
+            Color::parse("rgb(100%,0%,50%)"),
+            Some((Color(255, 0, 128), 1.0))
+        );
+        assert_eq!(Color::parse("rgba(237,113,0,0.5)"), Some((orange, 0.5)));
+        assert_eq!(Color::parse("rgb(237 113 0 / 25%)"), Some((orange, 0.25)));
+        assert_eq!(Color::parse("DarkOrange"), Some((Color(255, 140, 0), 1.0)));
+        assert_eq!(Color::parse("transparent").map(|c| c.1), Some(0.0));
+        assert_eq!(
+            Color::parse("#ED71"),
+            Some((Color(0xEE, 0xDD, 0x77), 0x11 as f32 / 255.0))
+        );
+        assert_eq!(Color::parse("#ED710"), None);
+        assert_eq!(Color::parse("rgb(1,2)"), None);
+        assert_eq!(Color::parse("notacolour"), None);
+    }
+
+    #[test]
+    fn test_parse_paint() {
+        assert_eq!(Paint::parse("none"), Some(Paint::None));
+        assert_eq!(Paint::parse("currentColor"), Some(Paint::CurrentColor));
+        assert_eq!(
+            Paint::parse("url(#gradient) #000"),
+            Some(Paint::Url(
+                "gradient".to_string(),
+                Box::new(Paint::Color(Color(0, 0, 0), 1.0))
+            ))
+        );
+        assert_eq!(
+            Paint::parse("url('#gradient')"),
+            Some(Paint::Url("gradient".to_string(), Box::new(Paint::None)))
+        );
+        assert_eq!(
+            Paint::parse("url(other.svg#gradient) red"),
+            Some(Paint::Color(Color(255, 0, 0), 1.0))
+        );
+        assert_eq!(Paint::parse("url(#gradient) bad"), None);
+    }
+
+    #[test]
+    fn test_color_table() {
+        let mut table = ColorTable::default();
+        assert_eq!(table.name(Color(0xD4, 0x5B, 0x07)), "svgD45B07");
+        assert_eq!(table.name(Color(255, 255, 255)), "svgFFFFFF");
+        assert_eq!(table.name(Color(0xD4, 0x5B, 0x07)), "svgD45B07");
+        assert_eq!(
+            table.to_string(),
+            "\\definecolor{svgD45B07}{HTML}{D45B07}\n\\definecolor{svgFFFFFF}{HTML}{FFFFFF}\n"
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! A small CSS engine, applying `<style>` sheets and `style` attributes to
+//! the element tree. Only type, class, id and descendant selectors are
+//! understood; rules using anything else are skipped, as are declarations
+//! with values that aren't (such as `var()`), which CSS would drop as well.
+
+use svg::node::Value;
+
+use crate::dom::Element;
+use crate::style::{Style, PROPERTIES};
+use crate::viewport::Viewport;
+
+/// A compound selector such as `path.cls-1`, matching a single element
+#[derive(Debug, Default, PartialEq)]
+struct Compound {
+    /// The element name, or `None` for `*` or where it's left out
+    name: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+/// Compound selectors, each of which must match a descendant of an element
+/// matched by the one before it
+#[derive(Debug, PartialEq)]
+struct Selector(Vec<Compound>);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Declaration {
+    property: String,
+    value: String,
+    important: bool,
+}
+
+#[derive(Debug)]
+struct Rule {
+    selector: Selector,
+    declarations: Vec<Declaration>,
+}
+
+/// The rules of every `<style>` in the document, in document order
+#[derive(Debug, Default)]
+struct Stylesheet {
+    rules: Vec<Rule>,
+}
+
+/// What selectors can match an element by
+struct Subject {
+    name: String,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+impl Subject {
+    fn of(element: &Element) -> Self {
+        Subject {
+            name: element.name.to_string(),
+            id: element.attr("id").map(str::to_string),
+            classes: element
+                .attr("class")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl Compound {
+    fn parse(s: &str) -> Option<Self> {
+        let mut compound = Compound::default();
+        let s = s.strip_prefix('*').unwrap_or(s);
+        let is_name_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+        let name_end = s.find(|c| !is_name_char(c)).unwrap_or(s.len());
+        if name_end > 0 {
+            compound.name = Some(s[..name_end].to_string());
+        }
+        let mut rest = &s[name_end..];
+        while let Some(prefix) = rest.chars().next() {
+            let start = prefix.len_utf8();
+            let end = rest[start..]
+                .find(|c| !is_name_char(c))
+                .map_or(rest.len(), |i| i + start);
+            let name = &rest[start..end];
+            match prefix {
+                _ if name.is_empty() => return None,
+                '.' => compound.classes.push(name.to_string()),
+                '#' => compound.id = Some(name.to_string()),
+                // attribute selectors, pseudo-classes, ...
+                _ => return None,
+            }
+            rest = &rest[end..];
+        }
+        Some(compound)
+    }
+
+    fn matches(&self, subject: &Subject) -> bool {
+        self.name.as_ref().is_none_or(|name| *name == subject.name)
+            && self
+                .id
+                .as_ref()
+                .is_none_or(|id| Some(id) == subject.id.as_ref())
+            && self
+                .classes
+                .iter()
+                .all(|class| subject.classes.contains(class))
+    }
+}
+
+impl Selector {
+    /// Parses a selector, giving `None` if it uses anything other than
+    /// descendant combinators
+    fn parse(s: &str) -> Option<Self> {
+        let compounds = s
+            .split_whitespace()
+            .map(Compound::parse)
+            .collect::<Option<Vec<_>>>()?;
+        (!compounds.is_empty()).then_some(Selector(compounds))
+    }
+
+    /// The number of id, class and type selectors, which decides between
+    /// rules setting the same property
+    fn specificity(&self) -> (usize, usize, usize) {
+        self.0.iter().fold((0, 0, 0), |(ids, classes, names), c| {
+            (
+                ids + c.id.iter().count(),
+                classes + c.classes.len(),
+                names + c.name.iter().count(),
+            )
+        })
+    }
+
+    /// Whether this matches `subject`, given its ancestors from the root
+    /// down
+    fn matches(&self, subject: &Subject, ancestors: &[Subject]) -> bool {
+        let (last, rest) = match self.0.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        if !last.matches(subject) {
+            return false;
+        }
+        // match the remaining compounds against the nearest ancestors that
+        // fit, working outwards
+        let mut ancestors = ancestors.iter().rev();
+        rest.iter()
+            .rev()
+            .all(|compound| ancestors.any(|ancestor| compound.matches(ancestor)))
+    }
+}
+
+/// Parses a declaration block such as `fill:#fff;stroke:none`, skipping
+/// anything malformed
+fn parse_declarations(block: &str) -> Vec<Declaration> {
+    block
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let value = value.trim();
+            let (value, important) = match value
+                .strip_suffix("important")
+                .and_then(|v| v.trim_end().strip_suffix('!'))
+            {
+                Some(value) => (value.trim_end(), true),
+                None => (value, false),
+            };
+            Some(Declaration {
+                property: property.trim().to_ascii_lowercase(),
+                value: value.to_string(),
+                important,
+            })
+        })
+        .filter(|declaration| !declaration.property.is_empty() && !declaration.value.is_empty())
+        .collect()
+}
+
+/// Removes `/* comments */`
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The rest of `css` after the block it starts with, allowing for nested
+/// blocks
+fn skip_block(css: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in css.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth <= 1 => return &css[i + 1..],
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    ""
+}
+
+impl Stylesheet {
+    fn parse(css: &str) -> Self {
+        let css = strip_comments(css);
+        let mut rules = Vec::new();
+        let mut rest = css.as_str();
+        loop {
+            rest = rest.trim_start();
+            let open = match rest.find('{') {
+                Some(open) => open,
+                None => break,
+            };
+            if rest.starts_with('@') {
+                // at-rules are either statements ending in `;`, like
+                // @import, or have a block, like @media. Neither is applied.
+                rest = match rest[..open].find(';') {
+                    Some(end) => &rest[end + 1..],
+                    None => skip_block(&rest[open..]),
+                };
+                continue;
+            }
+            let close = rest[open..].find('}').map_or(rest.len(), |i| open + i);
+            let declarations = parse_declarations(&rest[open + 1..close]);
+            for selector in rest[..open].split(',').filter_map(Selector::parse) {
+                rules.push(Rule {
+                    selector,
+                    declarations: declarations.clone(),
+                });
+            }
+            rest = rest.get(close + 1..).unwrap_or_default();
+        }
+        Stylesheet { rules }
+    }
+
+    /// Sets the properties the cascade gives `element` and its descendants
+    /// as their attributes, so they take the place of any presentation
+    /// attributes
+    fn cascade(&self, element: &mut Element, ancestors: &mut Vec<Subject>) {
+        let subject = Subject::of(element);
+        // later entries win: presentation attributes give way to rules,
+        // and rules to the style attribute, unless they are !important.
+        // Between rules, the more specific (and then the later) one wins.
+        let mut declarations: Vec<_> = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.selector.matches(&subject, ancestors))
+            .flat_map(|(order, rule)| {
+                let specificity = rule.selector.specificity();
+                rule.declarations
+                    .iter()
+                    .map(move |d| ((d.important, false, specificity, order), d.clone()))
+            })
+            .collect();
+        let inline = parse_declarations(element.attr("style").unwrap_or_default());
+        declarations.extend(
+            inline
+                .into_iter()
+                .map(|d| ((d.important, true, (0, 0, 0), 0), d)),
+        );
+        declarations.sort_by_key(|(precedence, _)| *precedence);
+        for (_, declaration) in declarations {
+            let property = declaration.property.as_str();
+            // percentages are resolved later, so any viewport will do here
+            let valid = PROPERTIES.contains(&property)
+                && Style::default()
+                    .set(property, declaration.value.trim(), &Viewport::default())
+                    .is_some();
+            if valid {
+                element
+                    .attributes
+                    .insert(declaration.property, Value::from(declaration.value));
+            }
+        }
+
+        ancestors.push(subject);
+        for child in &mut element.children {
+            self.cascade(child, ancestors);
+        }
+        ancestors.pop();
+    }
+}
+
+/// Applies the document's `<style>` sheets and the `style` attributes of
+/// its elements to the tree under `root`
+pub(crate) fn apply(root: &mut Element) {
+    fn collect_sheets(element: &Element, css: &mut String) {
+        if element.name == "style" && element.attr("type").is_none_or(|t| t == "text/css") {
+            css.push_str(&element.text);
+            css.push('\n');
+        }
+        for child in &element.children {
+            collect_sheets(child, css);
+        }
+    }
+    let mut css = String::new();
+    collect_sheets(root, &mut css);
+    Stylesheet::parse(&css).cascade(root, &mut Vec::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_svg;
+
+    #[test]
+    fn test_parse_selectors() {
+        let selector = Selector::parse("g#icon  path.cls-1.big").unwrap();
+        assert_eq!(selector.specificity(), (1, 2, 2));
+        assert_eq!(
+            selector.0[1],
+            Compound {
+                name: Some("path".to_string()),
+                id: None,
+                classes: vec!["cls-1".to_string(), "big".to_string()],
+            }
+        );
+        assert_eq!(
+            Selector::parse("*").map(|s| s.specificity()),
+            Some((0, 0, 0))
+        );
+        assert!(Selector::parse("g > path").is_none());
+        assert!(Selector::parse("a:hover").is_none());
+        assert!(Selector::parse(".").is_none());
+        assert!(Selector::parse("path»").is_none());
+        assert!(Selector::parse(".größe").is_some());
+    }
+
+    #[test]
+    fn test_parse_stylesheet() {
+        let sheet = Stylesheet::parse(
+            "/* exported */ @import url(x.css); @media print { .a { fill: red } }
+             .cls-1, .cls-2 { fill: #fff; stroke : none !important } g>a{fill:red}",
+        );
+        assert_eq!(sheet.rules.len(), 2);
+        assert_eq!(
+            sheet.rules[1].declarations,
+            vec![
+                Declaration {
+                    property: "fill".to_string(),
+                    value: "#fff".to_string(),
+                    important: false,
+                },
+                Declaration {
+                    property: "stroke".to_string(),
+                    value: "none".to_string(),
+                    important: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cascade() -> anyhow::Result<()> {
+        let svg = r##"<svg>
+            <defs><style><![CDATA[
+                .cls-1 { fill: #ED7100 }
+                #special { fill: #FF0000 }
+                path { fill: #00FF00 !important }
+                .icon .inner { fill-rule: evenodd }
+            ]]></style></defs>
+            <path class="cls-1" d="M0,0"/>
+            <path class="cls-1" id="special" fill="blue" d="M0,0"/>
+            <g class="icon"><g><path class="inner" style="fill:#0000FF" d="M0,0"/></g></g>
+            <path class="inner" style="fill: blue" d="M0,0"/>
+        </svg>"##;
+        let draws: Vec<_> = parse_svg(svg.as_bytes())?
+            .draws()
+            .iter()
+            .map(|draw| draw.attributes[0].to_string())
+            .collect();
+        // the !important type rule beats everything else
+        assert_eq!(draws, vec!["fill=svg00FF00"; 4]);
+
+        let svg = r##"<svg>
+            <style>.cls-1 { fill: #ED7100 } #special { fill: #FF0000 }</style>
+            <path class="cls-1" fill="blue" d="M0,0"/>
+            <path class="cls-1" id="special" d="M0,0"/>
+            <path class="cls-1" id="special" style="fill:#0000FF" d="M0,0"/>
+            <g class="cls-1"><path d="M0,0"/></g>
+        </svg>"##;
+        let draws: Vec<_> = parse_svg(svg.as_bytes())?
+            .draws()
+            .iter()
+            .map(|draw| draw.attributes[0].to_string())
+            .collect();
+        assert_eq!(
+            draws,
+            vec![
+                "fill=svgED7100",
+                "fill=svgFF0000",
+                "fill=svg0000FF",
+                "fill=svgED7100"
+            ]
+        );
+
+        let svg = r#"<svg><style>.icon .inner { fill-rule: evenodd }</style>
+            <g class="icon"><g><path class="inner" d="M0,0"/></g></g>
+            <path class="inner" d="M0,0"/>
+        </svg>"#;
+        let rules: Vec<_> = parse_svg(svg.as_bytes())?
+            .draws()
+            .iter()
+            .map(|draw| draw.attributes[1].to_string())
+            .collect();
+        assert_eq!(rules, vec!["even odd rule", "nonzero rule"]);
+
+        // a rule with a selector that isn't understood is skipped
+        let svg = r#"<svg><style>path» {fill:red}</style><path d="M0,0"/></svg>"#;
+        let tikz = parse_svg(svg.as_bytes())?;
+        assert_eq!(tikz.draws()[0].attributes[0].to_string(), "fill=svg000000");
+
+        // as are declarations with values that aren't understood, leaving
+        // the presentation attribute
+        let svg = r#"<svg><style>path { fill: var(--x) }</style>
+            <path fill="red" style="fill: hsl(0, 0%, 0%)" d="M0,0"/></svg>"#;
+        let tikz = parse_svg(svg.as_bytes())?;
+        assert_eq!(tikz.draws()[0].attributes[0].to_string(), "fill=svgFF0000");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! A minimal element tree built from the svg crate's event stream, so the
+//! document can be walked with its group structure intact
+
+use svg::node::element::tag::Type;
+use svg::node::Attributes;
+use svg::parser::Event;
+
+use crate::viewport::Viewport;
+use crate::{ConversionError, ElementRef};
+
+#[derive(Debug)]
+pub(crate) struct Element<'l> {
+    pub name: &'l str,
+    pub attributes: Attributes,
+    pub children: Vec<Element<'l>>,
+    /// Text content directly inside the element, e.g. of a `<title>`
+    pub text: String,
+    /// 1-based line of the element's start tag
+    pub line: usize,
+}
+
+impl<'l> Element<'l> {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|v| &**v)
+    }
+
+    pub fn reference(&self) -> ElementRef {
+        ElementRef {
+            tag: self.name.to_string(),
+            id: self.attr("id").map(str::to_string),
+            line: self.line,
+        }
+    }
+
+    /// An error for an attribute of this element that can't be understood
+    pub fn malformed(&self, attribute: &'static str) -> ConversionError {
+        ConversionError::MalformedAttribute {
+            element: self.reference(),
+            attribute,
+            value: self.attr(attribute).unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Parses a length attribute, if present, into user units, with
+    /// percentages of the `viewport`
+    pub fn length(
+        &self,
+        attribute: &'static str,
+        viewport: &Viewport,
+    ) -> Result<Option<f32>, ConversionError> {
+        match self.attr(attribute) {
+            Some(value) => match viewport.length(attribute, value) {
+                Some(length) => Ok(Some(length)),
+                None => Err(self.malformed(attribute)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up an attribute this element can't be drawn without
+    pub fn required(&self, attribute: &'static str) -> Result<&str, ConversionError> {
+        self.attr(attribute)
+            .ok_or_else(|| ConversionError::MissingAttribute {
+                element: self.reference(),
+                attribute,
+            })
+    }
+}
+
+/// The line `name` is on. The parser hands out tag names as slices of
+/// `input`, so their offset within it gives the position of the tag.
+fn line_of(input: &str, name: &str) -> usize {
+    let offset = (name.as_ptr() as usize).wrapping_sub(input.as_ptr() as usize);
+    match input.get(..offset) {
+        Some(before) => before.matches('\n').count() + 1,
+        None => 0,
+    }
+}
+
+/// Parses `input` into its root element
+pub(crate) fn parse(input: &str) -> Result<Element<'_>, ConversionError> {
+    let mut open: Vec<Element> = Vec::new();
+    let mut root = None;
+    for event in svg::read(input)? {
+        let finished = match event {
+            Event::Tag(name, kind, attributes) => {
+                let line = line_of(input, name);
+                match kind {
+                    Type::Start => {
+                        open.push(Element {
+                            name,
+                            attributes,
+                            children: Vec::new(),
+                            text: String::new(),
+                            line,
+                        });
+                        None
+                    }
+                    Type::Empty => Some(Element {
+                        name,
+                        attributes,
+                        children: Vec::new(),
+                        text: String::new(),
+                        line,
+                    }),
+                    Type::End => match open.pop() {
+                        Some(element) if element.name == name => Some(element),
+                        _ => {
+                            return Err(svg::parser::Error::new(
+                                (line, 0),
+                                format!("found an unexpected end tag '{}'", name),
+                            )
+                            .into())
+                        }
+                    },
+                }
+            }
+            Event::Text(text) => {
+                if let Some(parent) = open.last_mut() {
+                    parent.text.push_str(text);
+                }
+                None
+            }
+            // the parser doesn't know CDATA sections, which keep <style>
+            // sheets from being read as markup, and sees them as
+            // declarations
+            Event::Declaration(declaration) => {
+                if let (Some(parent), Some(text)) =
+                    (open.last_mut(), declaration.strip_prefix("<![CDATA["))
+                {
+                    parent
+                        .text
+                        .push_str(text.strip_suffix("]]>").unwrap_or(text));
+                }
+                None
+            }
+            Event::Error(e) => return Err(e.into()),
+            _ => None,
+        };
+        if let Some(element) = finished {
+            match open.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => root = root.or(Some(element)),
+            }
+        }
+    }
+    match (root, open.pop()) {
+        (Some(root), None) => Ok(root),
+        (_, Some(unclosed)) => Err(svg::parser::Error::new(
+            (unclosed.line, 0),
+            format!("found an unclosed tag '{}'", unclosed.name),
+        )
+        .into()),
+        (None, None) => Err(svg::parser::Error::new((0, 0), "found no elements").into()),
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt::Display;
+
+/// Identifies the SVG element a conversion problem was found in
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ElementRef {
+    pub tag: String,
+    pub id: Option<String>,
+    /// 1-based line of the element's start tag, or 0 if unknown
+    pub line: usize,
+}
+
+impl Display for ElementRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.id {
+            Some(id) => write!(f, "<{} id=\"{}\">", self.tag, id)?,
+            None => write!(f, "<{}>", self.tag)?,
+        }
+        if self.line > 0 {
+            write!(f, " on line {}", self.line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything that can stop an SVG from being converted
+#[derive(Debug)]
+pub enum ConversionError {
+    /// The input could not be read
+    Io(std::io::Error),
+    /// The input is not well-formed SVG
+    Parse(svg::parser::Error),
+    /// An element lacks an attribute it cannot be drawn without
+    MissingAttribute {
+        element: ElementRef,
+        attribute: &'static str,
+    },
+    /// An attribute's value could not be understood
+    MalformedAttribute {
+        element: ElementRef,
+        attribute: &'static str,
+        value: String,
+    },
+    /// Path data uses a command letter that is not part of SVG
+    UnsupportedCommand { element: ElementRef, command: char },
+    /// Path data could not be parsed, or a command has the wrong number of
+    /// parameters
+    MalformedPathData { element: ElementRef, reason: String },
+    /// A `<use>` refers to an id that isn't in the document
+    UnresolvedReference { element: ElementRef, id: String },
+    /// A `<use>` ends up, directly or indirectly, instantiating itself
+    CircularReference { element: ElementRef, id: String },
+    /// A drawing element that has no tikz equivalent (yet)
+    UnsupportedElement { element: ElementRef },
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::Io(e) => write!(f, "failed to read input: {}", e),
+            ConversionError::Parse(e) => write!(f, "invalid SVG: {}", e),
+            ConversionError::MissingAttribute { element, attribute } => {
+                write!(f, "{} is missing the '{}' attribute", element, attribute)
+            }
+            ConversionError::MalformedAttribute {
+                element,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "{} has a malformed '{}' attribute: \"{}\"",
+                element, attribute, value
+            ),
+            ConversionError::UnsupportedCommand { element, command } => {
+                write!(f, "{} uses unsupported path command '{}'", element, command)
+            }
+            ConversionError::MalformedPathData { element, reason } => {
+                write!(f, "{} has malformed path data: {}", element, reason)
+            }
+            ConversionError::UnresolvedReference { element, id } => {
+                write!(f, "{} refers to unknown id '{}'", element, id)
+            }
+            ConversionError::CircularReference { element, id } => {
+                write!(f, "{} refers to '{}', which contains itself", element, id)
+            }
+            ConversionError::UnsupportedElement { element } => {
+                write!(f, "{} cannot be converted", element)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConversionError::Io(e) => Some(e),
+            ConversionError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConversionError {
+    fn from(e: std::io::Error) -> Self {
+        ConversionError::Io(e)
+    }
+}
+
+impl From<svg::parser::Error> for ConversionError {
+    fn from(e: svg::parser::Error) -> Self {
+        ConversionError::Parse(e)
+    }
+}
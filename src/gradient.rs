@@ -0,0 +1,384 @@
+//! Conversion of `<linearGradient>` and `<radialGradient>` fills into pgf
+//! shadings. Tikz stretches a shading over the bounding box of the path it
+//! fills, so the gradient vector only decides the shading's direction (and
+//! the focal point of a radial one), not where along the path it starts.
+//! Stop opacities can't be expressed and are ignored, and stop colours
+//! can't be replaced by the style keys of `Recolor`, since pgf declares
+//! shadings with colours only. Strokes can't be shaded at all, and are
+//! drawn in a gradient's last colour.
+
+use std::fmt::Display;
+
+use crate::color::{Color, Paint};
+use crate::dom::Element;
+use crate::{parse_length, Attribute, Context, ConversionError, Converter, Point, Transform};
+
+/// The shadings used in a picture, in order of first use, each with the name
+/// it's declared under and its declaration
+#[derive(Debug, Default)]
+pub(crate) struct ShadingTable(Vec<(String, String)>);
+
+impl ShadingTable {
+    /// Declares a shading, named after `base` unless that is taken by a
+    /// different one, and gives the name it's declared under.
+    /// `declaration` makes a declaration given the name.
+    fn declare(&mut self, base: &str, declaration: impl Fn(&str) -> String) -> String {
+        let mut name = base.to_string();
+        for n in 2.. {
+            match self.0.iter().find(|(existing, _)| *existing == name) {
+                Some((_, existing)) if *existing == declaration(&name) => return name,
+                Some(_) => name = format!("{}-{}", base, n),
+                None => break,
+            }
+        }
+        self.0.push((name.clone(), declaration(&name)));
+        name
+    }
+}
+
+impl Display for ShadingTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (_, declaration) in &self.0 {
+            writeln!(f, "{}", declaration)?;
+        }
+        Ok(())
+    }
+}
+
+/// A gradient attribute value, either a fraction (possibly given as a
+/// percentage) of the bounding box or a length in user space
+fn coordinate(value: &str) -> Option<f32> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok().map(|p| p / 100.0),
+        None => parse_length(value),
+    }
+}
+
+/// Formats stops as a pgf colour specification, with offsets mapped onto
+/// `start..end` bp and the first and last colours padded out to `0bp` and
+/// `outer` bp
+fn color_specification(stops: &[(f32, String)], start: f32, end: f32, outer: f32) -> String {
+    let mut spec = Vec::new();
+    let mut last = -1.0;
+    let mut push = |position: f32, color: &str| {
+        // pgf needs the positions to increase strictly, so sharp
+        // transitions are given a sliver of width
+        let position = position.max(last + 0.01);
+        spec.push(format!("color({:.4}bp)=({})", position, color));
+        last = position;
+    };
+    if stops[0].0 > 0.0 || start > 0.0 {
+        push(0.0, &stops[0].1);
+    }
+    for (offset, color) in stops {
+        push(start + offset * (end - start), color);
+    }
+    push(outer, &stops[stops.len() - 1].1);
+    spec.join("; ")
+}
+
+impl<'a> Converter<'a> {
+    /// The gradient `element` and those it inherits from through its
+    /// `href`, nearest first
+    fn gradient_chain(&self, element: &'a Element<'a>) -> Vec<&'a Element<'a>> {
+        let mut chain = vec![element];
+        while let Some(href) = chain[chain.len() - 1]
+            .attr("href")
+            .or_else(|| chain[chain.len() - 1].attr("xlink:href"))
+        {
+            match href.strip_prefix('#').and_then(|id| self.ids.get(id)) {
+                Some(&next)
+                    if next.name.ends_with("Gradient")
+                        && !chain.iter().any(|e| std::ptr::eq(*e, next)) =>
+                {
+                    chain.push(next)
+                }
+                _ => break,
+            }
+        }
+        chain
+    }
+
+    /// The gradient with the given `id`, if there is one
+    fn gradient(&self, id: &str) -> Option<&'a Element<'a>> {
+        match self.ids.get(id) {
+            Some(&gradient) if gradient.name == "linearGradient" => Some(gradient),
+            Some(&gradient) if gradient.name == "radialGradient" => Some(gradient),
+            _ => None,
+        }
+    }
+
+    /// The stops of the gradient `chain`, as offsets and colours
+    fn gradient_stops(
+        &self,
+        chain: &[&Element],
+        context: &Context,
+    ) -> Result<Vec<(f32, Color)>, ConversionError> {
+        let mut stops: Vec<(f32, Color)> = Vec::new();
+        let stop_elements = chain
+            .iter()
+            .map(|e| {
+                e.children
+                    .iter()
+                    .filter(|c| c.name == "stop")
+                    .collect::<Vec<_>>()
+            })
+            .find(|stops| !stops.is_empty())
+            .unwrap_or_default();
+        for stop in stop_elements {
+            let offset = match stop.attr("offset") {
+                Some(value) => coordinate(value).ok_or_else(|| stop.malformed("offset"))?,
+                None => 0.0,
+            };
+            // offsets can't go backwards
+            let offset = offset
+                .clamp(0.0, 1.0)
+                .max(stops.last().map_or(0.0, |s| s.0));
+            let color = match stop.attr("stop-color").map(str::trim) {
+                None => Color(0, 0, 0),
+                Some(value) if value.eq_ignore_ascii_case("currentcolor") => {
+                    context.style.color.map_or(Color(0, 0, 0), |c| c.0)
+                }
+                Some(value) => {
+                    Color::parse(value)
+                        .ok_or_else(|| stop.malformed("stop-color"))?
+                        .0
+                }
+            };
+            stops.push((offset, color));
+        }
+        Ok(stops)
+    }
+
+    /// The paint stroking a shape with the gradient with the given `id`, or
+    /// `None` if there's no such gradient. Tikz can't stroke with a
+    /// shading, so the stroke takes the gradient's last colour.
+    pub(crate) fn gradient_stroke(
+        &self,
+        id: &str,
+        context: &Context,
+    ) -> Result<Option<Paint>, ConversionError> {
+        let gradient = match self.gradient(id) {
+            Some(gradient) => gradient,
+            None => return Ok(None),
+        };
+        let stops = self.gradient_stops(&self.gradient_chain(gradient), context)?;
+        Ok(Some(match stops.last() {
+            Some(&(_, color)) => Paint::Color(color, 1.0),
+            // a gradient without stops paints nothing
+            None => Paint::None,
+        }))
+    }
+
+    /// The attributes filling a shape with the gradient with the given `id`,
+    /// or `None` if there's no such gradient, so that the fill's fallback
+    /// applies instead
+    pub(crate) fn gradient_attributes(
+        &self,
+        id: &str,
+        context: &Context,
+    ) -> Result<Option<Vec<Attribute>>, ConversionError> {
+        let gradient = match self.gradient(id) {
+            Some(gradient) => gradient,
+            None => return Ok(None),
+        };
+        let chain = self.gradient_chain(gradient);
+        // the nearest gradient that has the attribute, along with it
+        let lookup = |attribute| {
+            chain
+                .iter()
+                .find_map(|e| e.attr(attribute).map(|value| (*e, value)))
+        };
+        let number = |attribute: &'static str, default: f32| match lookup(attribute) {
+            Some((element, value)) => coordinate(value).ok_or_else(|| element.malformed(attribute)),
+            None => Ok(default),
+        };
+
+        let stops: Vec<(f32, String)> = {
+            let mut colors = self.colors.borrow_mut();
+            self.gradient_stops(&chain, context)?
+                .into_iter()
+                .map(|(offset, color)| (offset, colors.name(color)))
+                .collect()
+        };
+        let opacity =
+            context.style.fill_opacity.unwrap_or(1.0) * context.style.opacity.unwrap_or(1.0);
+        // the attributes, at the fill's opacity
+        let with_opacity = |mut attributes: Vec<Attribute>| {
+            if opacity < 1.0 {
+                attributes.push(Attribute::param("fill opacity", format!("{:.4}", opacity)));
+            }
+            Ok(Some(attributes))
+        };
+        let solid = |color: Option<&(f32, String)>| match color {
+            Some((_, color)) => with_opacity(vec![Attribute::param("fill", color.clone())]),
+            None => Ok(Some(vec![Attribute::param("fill", "none")])),
+        };
+        if stops.len() < 2 {
+            return solid(stops.first());
+        }
+
+        let gradient_transform = match lookup("gradientTransform") {
+            Some((element, value)) => {
+                Transform::parse(value).ok_or_else(|| element.malformed("gradientTransform"))?
+            }
+            None => Transform::identity(),
+        };
+        let transform = context.canvas.compose(&gradient_transform);
+        // the direction of a vector in the gradient's space, in tikz
+        // coordinates
+        let direction = |dx: f32, dy: f32| {
+            let Point(x, y) = transform.apply(Point(dx, dy));
+            let Point(x0, y0) = transform.apply(Point(0.0, 0.0));
+            (x - x0, y - y0)
+        };
+        let base = format!(
+            "svg-{}",
+            id.chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect::<String>()
+        );
+        let mut shadings = self.shadings.borrow_mut();
+
+        let attributes = if gradient.name == "linearGradient" {
+            let (x1, y1) = (number("x1", 0.0)?, number("y1", 0.0)?);
+            let (x2, y2) = (number("x2", 1.0)?, number("y2", 0.0)?);
+            let (dx, dy) = direction(x2 - x1, y2 - y1);
+            if dx == 0.0 && dy == 0.0 {
+                // a gradient vector of no length paints the last colour
+                return solid(stops.last());
+            }
+            // tikz shows the middle 50bp of a shading, so the stops go there
+            let spec = color_specification(&stops, 25.0, 75.0, 100.0);
+            let name = shadings.declare(&base, |name| {
+                format!(
+                    "\\pgfdeclarehorizontalshading{{{}}}{{100bp}}{{{}}}",
+                    name, spec
+                )
+            });
+            vec![
+                Attribute::param("shading", name),
+                Attribute::param("shading angle", format!("{:.4}", dy.atan2(dx).to_degrees())),
+            ]
+        } else {
+            let (cx, cy, r) = (number("cx", 0.5)?, number("cy", 0.5)?, number("r", 0.5)?);
+            let (fx, fy) = (number("fx", cx)?, number("fy", cy)?);
+            let scale = transform.mean_scale();
+            // a radius of no size, before or after the transformation,
+            // paints the last colour
+            if r <= 0.0 || scale == 0.0 {
+                return solid(stops.last());
+            }
+            // the focal point, relative to the centre as 25bp is to the
+            // radius
+            let (dx, dy) = direction(fx - cx, fy - cy);
+            let focus = 25.0 / (r * scale);
+            let spec = color_specification(&stops, 0.0, 25.0, 50.0);
+            let name = shadings.declare(&base, |name| {
+                format!(
+                    "\\pgfdeclareradialshading{{{}}}{{\\pgfpoint{{{:.4}bp}}{{{:.4}bp}}}}{{{}}}",
+                    name,
+                    dx * focus,
+                    dy * focus,
+                    spec
+                )
+            });
+            vec![Attribute::param("shading", name)]
+        };
+        with_opacity(attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{attributes_to_tikz, parse_svg};
+
+    #[test]
+    fn test_linear_gradient() -> anyhow::Result<()> {
+        let svg = r##"<svg viewBox="0 0 10 10">
+            <defs>
+                <linearGradient id="base" x2="0" y2="1">
+                    <stop offset="0" stop-color="#ED7100"/>
+                    <stop offset="50%" style="stop-color:#FFFFFF"/>
+                    <stop offset="0.2" stop-color="#000000"/>
+                </linearGradient>
+                <linearGradient id="rotated" href="#base" gradientTransform="rotate(-90)"/>
+            </defs>
+            <rect width="10" height="10" fill="url(#base)"/>
+            <rect width="10" height="10" fill="url(#rotated)"/>
+            <rect width="10" height="10" fill="url(#missing) red"/>
+            <rect width="10" height="10" fill="none" stroke="url(#base)"/>
+        </svg>"##;
+        let tikz = parse_svg(svg.as_bytes())?;
+        let attributes: Vec<_> = tikz
+            .draws()
+            .iter()
+            .map(|draw| attributes_to_tikz(&draw.attributes))
+            .collect();
+        // downwards in the SVG is downwards in tikz too
+        assert_eq!(
+            attributes[0],
+            "nonzero rule,draw=none,shading=svg-base,shading angle=-90.0000"
+        );
+        assert_eq!(
+            attributes[1],
+            "nonzero rule,draw=none,shading=svg-rotated,shading angle=0.0000"
+        );
+        assert!(attributes[2].starts_with("fill=svgFF0000,"));
+        // strokes can't be shaded, and take the last colour instead
+        assert!(attributes[3].starts_with("fill=none,nonzero rule,draw=svg000000,"));
+        // the out of order stop is moved up to the one before, with a
+        // sliver between them
+        assert!(tikz.to_string().contains(
+            "\\pgfdeclarehorizontalshading{svg-base}{100bp}{color(0.0000bp)=(svgED7100); \
+             color(25.0000bp)=(svgED7100); color(50.0000bp)=(svgFFFFFF); \
+             color(50.0100bp)=(svg000000); color(100.0000bp)=(svg000000)}\n"
+        ));
+        assert_eq!(tikz.to_string().matches("\\pgfdeclare").count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_radial_gradient() -> anyhow::Result<()> {
+        let svg = r##"<svg viewBox="0 0 10 10">
+            <radialGradient id="glow" fx="0.75">
+                <stop offset="0" stop-color="white"/>
+                <stop offset="1" stop-color="black"/>
+            </radialGradient>
+            <radialGradient id="single"><stop stop-color="red"/></radialGradient>
+            <circle r="5" fill="url(#glow)" fill-opacity="0.5"/>
+            <circle r="5" fill="url(#single)"/>
+            <circle r="5" fill="url(#flat)"/>
+            <circle r="5" fill="url(#single)" fill-opacity="0.5"/>
+            <radialGradient id="flat" href="#glow" gradientTransform="scale(0)"/>
+        </svg>"##;
+        let tikz = parse_svg(svg.as_bytes())?;
+        assert_eq!(
+            attributes_to_tikz(&tikz.draws()[0].attributes),
+            "nonzero rule,draw=none,shading=svg-glow,fill opacity=0.5000"
+        );
+        assert!(tikz.to_string().contains(
+            "\\pgfdeclareradialshading{svg-glow}{\\pgfpoint{12.5000bp}{0.0000bp}}\
+             {color(0.0000bp)=(svgFFFFFF); color(25.0000bp)=(svg000000); \
+             color(50.0000bp)=(svg000000)}\n"
+        ));
+        // a single stop is a plain fill
+        assert_eq!(
+            attributes_to_tikz(&tikz.draws()[1].attributes),
+            "nonzero rule,draw=none,fill=svgFF0000"
+        );
+        // as is one that is scaled down to nothing
+        assert_eq!(
+            attributes_to_tikz(&tikz.draws()[2].attributes),
+            "nonzero rule,draw=none,fill=svg000000"
+        );
+        // both keep the fill's opacity
+        assert_eq!(
+            attributes_to_tikz(&tikz.draws()[3].attributes),
+            "nonzero rule,draw=none,fill=svgFF0000,fill opacity=0.5000"
+        );
+
+        Ok(())
+    }
+}
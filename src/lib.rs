@@ -1,14 +1,125 @@
-use std::{fmt::Display, io::Read};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, io::Read};
 
-use svg::node::element::path::{Command, Data, Position::Absolute};
+use svg::node::element::path::{Command, Data, Position};
+
+mod color;
+mod css;
+mod dom;
+mod error;
+mod gradient;
+mod palette;
+mod reference;
+mod shapes;
+mod style;
+mod transform;
+mod variant;
+mod viewport;
+
+pub use color::Color;
+pub use error::{ConversionError, ElementRef};
+pub use transform::Transform;
+pub use variant::Variant;
+
+use color::{ColorTable, Paint};
+use dom::Element;
+use gradient::ShadingTable;
+use style::Style;
+use viewport::Viewport;
 
 /// Represents a single tikz `\draw` command
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct TikzDraw {
     attributes: Vec<Attribute>,
     path_sections: Vec<PathSection>,
 }
 
+/// Represents a tikz `scope` environment
+#[derive(Debug, Default)]
+pub struct TikzScope {
+    attributes: Vec<Attribute>,
+    /// Written after `\begin{scope}`, e.g. the id of the SVG group
+    comment: Option<String>,
+    items: Vec<TikzItem>,
+}
+
+#[derive(Debug)]
+pub enum TikzItem {
+    Draw(TikzDraw),
+    Scope(TikzScope),
+}
+
+/// A whole converted SVG: one tikz `\draw` per drawn SVG element, in
+/// document order
+#[derive(Debug, Default)]
+pub struct TikzPicture {
+    /// Colours to `\definecolor` before drawing
+    colors: ColorTable,
+    /// Shadings to declare, after the colours they use
+    shadings: ShadingTable,
+    variant: Option<Variant>,
+    items: Vec<TikzItem>,
+}
+
+impl TikzPicture {
+    pub fn items(&self) -> &[TikzItem] {
+        &self.items
+    }
+
+    /// The variant of AWS icon the picture is, as asked for in the options
+    /// or else as the SVG's `<title>` (or `Options::source_variant`) tells
+    pub fn variant(&self) -> Option<Variant> {
+        self.variant
+    }
+
+    /// Every draw in the picture, including those nested in scopes
+    pub fn draws(&self) -> Vec<&TikzDraw> {
+        fn collect<'a>(items: &'a [TikzItem], draws: &mut Vec<&'a TikzDraw>) {
+            for item in items {
+                match item {
+                    TikzItem::Draw(draw) => draws.push(draw),
+                    TikzItem::Scope(scope) => collect(&scope.items, draws),
+                }
+            }
+        }
+        let mut draws = Vec::new();
+        collect(&self.items, &mut draws);
+        draws
+    }
+}
+
+fn write_items(
+    f: &mut std::fmt::Formatter<'_>,
+    items: &[TikzItem],
+    indent: usize,
+) -> std::fmt::Result {
+    for item in items {
+        match item {
+            TikzItem::Draw(draw) => writeln!(f, "{:indent$}{}", "", draw, indent = indent)?,
+            TikzItem::Scope(scope) => {
+                write!(f, "{:indent$}\\begin{{scope}}", "", indent = indent)?;
+                if !scope.attributes.is_empty() {
+                    write!(f, "[{}]", attributes_to_tikz(&scope.attributes))?;
+                }
+                match &scope.comment {
+                    Some(comment) => writeln!(f, " % {}", comment)?,
+                    None => writeln!(f)?,
+                }
+                write_items(f, &scope.items, indent + 2)?;
+                writeln!(f, "{:indent$}\\end{{scope}}", "", indent = indent)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Display for TikzPicture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.colors, self.shadings)?;
+        write_items(f, &self.items, 0)
+    }
+}
+
+#[derive(Debug)]
 pub enum Attribute {
     Setting(String),
     Param(String, String),
@@ -54,19 +165,38 @@ impl Display for TikzDraw {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Point(f32, f32);
 
+impl Point {
+    /// Resolves a coordinate pair against the current point, as given by the
+    /// position of the SVG command it came from
+    fn resolve(position: &Position, origin: Point, x: f32, y: f32) -> Self {
+        match position {
+            Position::Absolute => Point(x, y),
+            Position::Relative => Point(origin.0 + x, origin.1 + y),
+        }
+    }
+}
+
 impl Display for Point {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({:.4}, {:.4})", self.0, self.1)
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum PathSection {
     Move(Point),
     Line(Point),
     Curve(Point, Point, Point),
     Cycle,
+    /// A whole axis-aligned rectangle between two opposite corners
+    Rectangle(Point, Point),
+    /// A whole circle, by centre and radius
+    Circle(Point, f32),
+    /// A whole axis-aligned ellipse, by centre and x and y radius
+    Ellipse(Point, f32, f32),
 }
 
 impl Display for PathSection {
@@ -76,49 +206,802 @@ impl Display for PathSection {
             PathSection::Line(p) => write!(f, "--{}", p),
             PathSection::Curve(c1, c2, p) => write!(f, ".. controls {} and {} .. {}", c1, c2, p),
             PathSection::Cycle => write!(f, "--cycle"),
+            PathSection::Rectangle(p1, p2) => write!(f, "{} rectangle {}", p1, p2),
+            PathSection::Circle(c, r) => write!(f, "{} circle[radius={:.4}]", c, r),
+            PathSection::Ellipse(c, rx, ry) => {
+                write!(f, "{} ellipse[x radius={:.4},y radius={:.4}]", c, rx, ry)
+            }
         }
     }
 }
 
+/// Where the pen stands with respect to the subpath being drawn
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Subpath {
+    /// Nothing has been drawn yet; only a moveto is valid here
+    #[default]
+    NotStarted,
+    Open,
+    /// The last subpath was closed, leaving the pen at its start. Drawing
+    /// on without a moveto begins a new subpath from that same point.
+    Closed,
+}
+
+/// The pen state carried from one SVG path command to the next, used to
+/// resolve relative commands into absolute points
+#[derive(Default)]
+pub struct PathState {
+    /// The element whose path data is being walked, for error reporting
+    element: ElementRef,
+    current: Point,
+    subpath: Subpath,
+    subpath_start: Point,
+    /// Second control point of the previous segment, if it was a cubic curve
+    cubic_control: Option<Point>,
+    /// Control point of the previous segment, if it was a quadratic curve
+    quadratic_control: Option<Point>,
+}
+
+impl PathState {
+    pub fn new(element: ElementRef) -> Self {
+        PathState {
+            element,
+            ..Default::default()
+        }
+    }
+
+    /// The first control point of a smooth curve: the reflection of the
+    /// previous control point about the current point, or the current point
+    /// itself if the previous segment had no control point to reflect
+    fn reflect(&self, control: Option<Point>) -> Point {
+        match control {
+            Some(c) => Point(2.0 * self.current.0 - c.0, 2.0 * self.current.1 - c.1),
+            None => self.current,
+        }
+    }
+
+    /// Draws a quadratic curve from the current point as the exactly
+    /// equivalent cubic (degree elevation), moving the pen to `end`
+    fn quadratic_to(&mut self, control: Point, end: Point) -> PathSection {
+        let start = self.current;
+        let c1 = Point(
+            start.0 + 2.0 / 3.0 * (control.0 - start.0),
+            start.1 + 2.0 / 3.0 * (control.1 - start.1),
+        );
+        let c2 = Point(
+            end.0 + 2.0 / 3.0 * (control.0 - end.0),
+            end.1 + 2.0 / 3.0 * (control.1 - end.1),
+        );
+        self.current = end;
+        self.quadratic_control = Some(control);
+        PathSection::Curve(c1, c2, end)
+    }
+
+    /// Draws an elliptical arc from the current point to `end` as a sequence
+    /// of cubic curves, each spanning at most a quarter turn.
+    ///
+    /// This follows the endpoint to center parameterisation conversion from
+    /// the SVG spec (appendix B.2.4), including scaling up radii that are too
+    /// small to reach the end point.
+    fn arc_to(
+        &mut self,
+        radii: (f32, f32),
+        rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        end: Point,
+    ) -> Vec<PathSection> {
+        use std::f64::consts::{FRAC_PI_2, PI};
+
+        let start = self.current;
+        self.current = end;
+        if start == end {
+            return vec![];
+        }
+        let (mut rx, mut ry) = (radii.0.abs() as f64, radii.1.abs() as f64);
+        if rx == 0.0 || ry == 0.0 {
+            return vec![PathSection::Line(end)];
+        }
+
+        let (x1, y1) = (start.0 as f64, start.1 as f64);
+        let (x2, y2) = (end.0 as f64, end.1 as f64);
+        let (sin_phi, cos_phi) = (rotation as f64).to_radians().sin_cos();
+
+        // step 1: move the midpoint of the chord to the origin and undo the
+        // ellipse rotation
+        let (dx, dy) = ((x1 - x2) / 2.0, (y1 - y2) / 2.0);
+        let x1p = cos_phi * dx + sin_phi * dy;
+        let y1p = -sin_phi * dx + cos_phi * dy;
+
+        let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+        if lambda > 1.0 {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+
+        // step 2: the center in the rotated frame
+        let num = rx * rx * ry * ry - rx * rx * y1p * y1p - ry * ry * x1p * x1p;
+        let den = rx * rx * y1p * y1p + ry * ry * x1p * x1p;
+        let mut coef = (num / den).max(0.0).sqrt();
+        if large_arc == sweep {
+            coef = -coef;
+        }
+        let cxp = coef * rx * y1p / ry;
+        let cyp = -coef * ry * x1p / rx;
+
+        // step 3: the center in user space
+        let cx = cos_phi * cxp - sin_phi * cyp + (x1 + x2) / 2.0;
+        let cy = sin_phi * cxp + cos_phi * cyp + (y1 + y2) / 2.0;
+
+        // step 4: start angle and angular extent
+        let angle =
+            |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+        let (ux, uy) = ((x1p - cxp) / rx, (y1p - cyp) / ry);
+        let (vx, vy) = ((-x1p - cxp) / rx, (-y1p - cyp) / ry);
+        let theta1 = angle(1.0, 0.0, ux, uy);
+        let mut delta = angle(ux, uy, vx, vy);
+        if !sweep && delta > 0.0 {
+            delta -= 2.0 * PI;
+        } else if sweep && delta < 0.0 {
+            delta += 2.0 * PI;
+        }
+
+        let point = |t: f64| {
+            let (sin_t, cos_t) = t.sin_cos();
+            (
+                cx + rx * cos_t * cos_phi - ry * sin_t * sin_phi,
+                cy + rx * cos_t * sin_phi + ry * sin_t * cos_phi,
+            )
+        };
+        let tangent = |t: f64| {
+            let (sin_t, cos_t) = t.sin_cos();
+            (
+                -rx * sin_t * cos_phi - ry * cos_t * sin_phi,
+                -rx * sin_t * sin_phi + ry * cos_t * cos_phi,
+            )
+        };
+
+        let segments = (delta.abs() / FRAC_PI_2 - 1e-9).ceil().max(1.0) as usize;
+        let step = delta / segments as f64;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        (0..segments)
+            .map(|i| {
+                let t1 = theta1 + step * i as f64;
+                let t2 = t1 + step;
+                let (p1, d1) = (point(t1), tangent(t1));
+                let (p2, d2) = (point(t2), tangent(t2));
+                let c1 = Point((p1.0 + k * d1.0) as f32, (p1.1 + k * d1.1) as f32);
+                let c2 = Point((p2.0 - k * d2.0) as f32, (p2.1 - k * d2.1) as f32);
+                // land exactly on the requested end point
+                let p = if i + 1 == segments {
+                    end
+                } else {
+                    Point(p2.0 as f32, p2.1 as f32)
+                };
+                PathSection::Curve(c1, c2, p)
+            })
+            .collect()
+    }
+}
+
 impl PathSection {
-    pub fn from_svg(cmd: &Command) -> Self {
-        match cmd {
-            Command::Move(Absolute, params) => PathSection::Move(Point(params[0], params[1])),
-            Command::Line(Absolute, params) => PathSection::Line(Point(params[0], params[1])),
-            Command::CubicCurve(Absolute, params) => PathSection::Curve(
-                Point(params[0], params[1]),
-                Point(params[2], params[3]),
-                Point(params[4], params[5]),
-            ),
-            Command::Close => PathSection::Cycle,
-            _command => panic!("not yet supported: {:?}", cmd),
+    /// Converts a single SVG path command into tikz path sections, updating
+    /// the pen state as it goes. A command may carry several parameter sets
+    /// (e.g. `l 1 2 3 4`), so it may produce several sections.
+    pub fn from_svg(cmd: &Command, state: &mut PathState) -> Result<Vec<Self>, ConversionError> {
+        let arity = match cmd {
+            Command::HorizontalLine(_, params) | Command::VerticalLine(_, params) => {
+                Some((1, params))
+            }
+            Command::Move(_, params)
+            | Command::Line(_, params)
+            | Command::SmoothQuadraticCurve(_, params) => Some((2, params)),
+            Command::QuadraticCurve(_, params) | Command::SmoothCubicCurve(_, params) => {
+                Some((4, params))
+            }
+            Command::CubicCurve(_, params) => Some((6, params)),
+            Command::EllipticalArc(_, params) => Some((7, params)),
+            Command::Close => None,
+        };
+        if let Some((arity, params)) = arity {
+            if params.is_empty() || params.len() % arity != 0 {
+                let letter = String::from(cmd.clone()).chars().next().unwrap_or('?');
+                return Err(ConversionError::MalformedPathData {
+                    element: state.element.clone(),
+                    reason: format!(
+                        "'{}' takes parameters in groups of {}, but got {}",
+                        letter,
+                        arity,
+                        params.len()
+                    ),
+                });
+            }
         }
+
+        let restart = match (cmd, state.subpath) {
+            (Command::Move(..), _) => {
+                state.subpath = Subpath::Open;
+                None
+            }
+            (_, Subpath::NotStarted) => {
+                return Err(ConversionError::MalformedPathData {
+                    element: state.element.clone(),
+                    reason: "path data must begin with a moveto".to_string(),
+                });
+            }
+            // closing an already closed subpath draws nothing
+            (Command::Close, Subpath::Closed) => return Ok(vec![]),
+            (Command::Close, Subpath::Open) => {
+                state.subpath = Subpath::Closed;
+                None
+            }
+            (_, Subpath::Closed) => {
+                state.subpath = Subpath::Open;
+                Some(PathSection::Move(state.subpath_start))
+            }
+            (_, Subpath::Open) => None,
+        };
+
+        let sections = match cmd {
+            Command::Move(position, params) => params
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(i, p)| {
+                    let point = Point::resolve(position, state.current, p[0], p[1]);
+                    state.current = point;
+                    // subsequent pairs after a move are implicit linetos
+                    if i == 0 {
+                        state.subpath_start = point;
+                        PathSection::Move(point)
+                    } else {
+                        PathSection::Line(point)
+                    }
+                })
+                .collect(),
+            Command::Line(position, params) => params
+                .chunks_exact(2)
+                .map(|p| {
+                    state.current = Point::resolve(position, state.current, p[0], p[1]);
+                    PathSection::Line(state.current)
+                })
+                .collect(),
+            Command::HorizontalLine(position, params) => params
+                .iter()
+                .map(|&x| {
+                    state.current = match position {
+                        Position::Absolute => Point(x, state.current.1),
+                        Position::Relative => Point(state.current.0 + x, state.current.1),
+                    };
+                    PathSection::Line(state.current)
+                })
+                .collect(),
+            Command::VerticalLine(position, params) => params
+                .iter()
+                .map(|&y| {
+                    state.current = match position {
+                        Position::Absolute => Point(state.current.0, y),
+                        Position::Relative => Point(state.current.0, state.current.1 + y),
+                    };
+                    PathSection::Line(state.current)
+                })
+                .collect(),
+            Command::CubicCurve(position, params) => params
+                .chunks_exact(6)
+                .map(|p| {
+                    let c1 = Point::resolve(position, state.current, p[0], p[1]);
+                    let c2 = Point::resolve(position, state.current, p[2], p[3]);
+                    state.current = Point::resolve(position, state.current, p[4], p[5]);
+                    state.cubic_control = Some(c2);
+                    PathSection::Curve(c1, c2, state.current)
+                })
+                .collect(),
+            Command::SmoothCubicCurve(position, params) => params
+                .chunks_exact(4)
+                .map(|p| {
+                    let c1 = state.reflect(state.cubic_control);
+                    let c2 = Point::resolve(position, state.current, p[0], p[1]);
+                    state.current = Point::resolve(position, state.current, p[2], p[3]);
+                    state.cubic_control = Some(c2);
+                    PathSection::Curve(c1, c2, state.current)
+                })
+                .collect(),
+            Command::QuadraticCurve(position, params) => params
+                .chunks_exact(4)
+                .map(|p| {
+                    let control = Point::resolve(position, state.current, p[0], p[1]);
+                    let end = Point::resolve(position, state.current, p[2], p[3]);
+                    state.quadratic_to(control, end)
+                })
+                .collect(),
+            Command::SmoothQuadraticCurve(position, params) => params
+                .chunks_exact(2)
+                .map(|p| {
+                    let control = state.reflect(state.quadratic_control);
+                    let end = Point::resolve(position, state.current, p[0], p[1]);
+                    state.quadratic_to(control, end)
+                })
+                .collect(),
+            Command::EllipticalArc(position, params) => params
+                .chunks_exact(7)
+                .flat_map(|p| {
+                    let end = Point::resolve(position, state.current, p[5], p[6]);
+                    state.arc_to((p[0], p[1]), p[2], p[3] != 0.0, p[4] != 0.0, end)
+                })
+                .collect(),
+            Command::Close => {
+                state.current = state.subpath_start;
+                vec![PathSection::Cycle]
+            }
+        };
+        // only a C or S segment leaves a control point behind for S to
+        // reflect, and likewise only Q or T for T
+        if !matches!(cmd, Command::CubicCurve(..) | Command::SmoothCubicCurve(..)) {
+            state.cubic_control = None;
+        }
+        if !matches!(
+            cmd,
+            Command::QuadraticCurve(..) | Command::SmoothQuadraticCurve(..)
+        ) {
+            state.quadratic_control = None;
+        }
+        Ok(restart.into_iter().chain(sections).collect())
+    }
+}
+
+/// Drawing elements that are recognised but cannot be converted
+const UNSUPPORTED_ELEMENTS: &[&str] = &["text", "image", "foreignObject"];
+
+/// Parses the `d` attribute of a path into tikz path sections
+fn convert_path_data(d: &str, element: ElementRef) -> Result<Vec<PathSection>, ConversionError> {
+    // the svg parser rejects unknown letters with a generic message, so
+    // look for them first to give a more specific error
+    if let Some(command) = d
+        .chars()
+        .find(|c| c.is_ascii_alphabetic() && !"MmLlHhVvQqTtCcSsAaZzEe".contains(*c))
+    {
+        return Err(ConversionError::UnsupportedCommand { element, command });
+    }
+    let data = Data::parse(d).map_err(|e| ConversionError::MalformedPathData {
+        element: element.clone(),
+        reason: e.to_string(),
+    })?;
+    let mut state = PathState::new(element);
+    let mut sections = Vec::new();
+    for cmd in data.iter() {
+        sections.extend(PathSection::from_svg(cmd, &mut state)?);
+    }
+    Ok(sections)
+}
+
+/// Controls how SVG coordinates are mapped into tikz ones
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// SVG's y axis points down and tikz's points up, so by default the
+    /// icon is flipped vertically within its viewport (or `viewBox`) to come
+    /// out upright. Turn this off to keep SVG's orientation, e.g. to apply a
+    /// `yscale=-1` yourself.
+    pub flip_y: bool,
+    /// Fit the `viewBox` into the viewport given by the root element's
+    /// `width` and `height`, honouring `preserveAspectRatio`, so coordinates
+    /// are measured from the corner of the viewport. Turn this and `flip_y`
+    /// off to keep the raw SVG user space coordinates.
+    pub map_viewport: bool,
+    /// Scale the icon uniformly so the longer side of its viewport is this
+    /// long, e.g. `Some(1.0)` for a unit box. Only applies with
+    /// `map_viewport`.
+    pub scale_to: Option<f32>,
+    /// Whether `transform` attributes are applied to the coordinates, or
+    /// kept as tikz scopes
+    pub transform_mode: TransformMode,
+    /// Mirror the document structure by wrapping the content of every
+    /// (non-empty) `<g>` in a scope, commented with the group's id, so
+    /// parts of an icon can be found and restyled
+    pub group_scopes: bool,
+    /// Replace colours by tikz style keys, so documents can recolour icons
+    pub recolor: Option<Recolor>,
+    /// Produce this variant of an AWS icon, remapping its foreground colours
+    /// if its `<title>` (or else `source_variant`) says it's the other one
+    pub variant: Option<Variant>,
+    /// The variant the icon is, for icons without a `<title>` telling, as
+    /// e.g. `Variant::detect` finds from the file name. Icons of unknown
+    /// variant aren't remapped.
+    pub source_variant: Option<Variant>,
+    /// Name the colours of the AWS icon categories after their category
+    /// (`awscompute`, `awsstorage`, ...), and the darker glyph colours of
+    /// some categories' resource icons likewise (`awscomputefg`), declared
+    /// with `\providecolor` so a document can adjust a whole category at
+    /// once by defining them first
+    pub aws_palette: bool,
+}
+
+/// Colours to replace by tikz style keys. A key is used as `key` on fills
+/// and `key=draw` on strokes, and defined as `key/.style={#1=<colour>}`
+/// unless the document has already defined it, so that e.g.
+/// `\tikzset{aws icon fg/.style={#1=red}}` turns whatever it paints red.
+/// Gradient stops go into shading declarations, which can't use keys, so
+/// they keep their colours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recolor {
+    /// The key that replaces `currentColor`
+    pub current_color: String,
+    /// Further colours to replace, each with its key
+    pub colors: Vec<(Color, String)>,
+}
+
+impl Default for Recolor {
+    fn default() -> Self {
+        Recolor {
+            current_color: "aws icon fg".to_string(),
+            colors: Vec::new(),
+        }
+    }
+}
+
+/// How SVG `transform` attributes end up in the tikz output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TransformMode {
+    /// Apply every transformation to the points themselves, giving a flat
+    /// list of draws
+    #[default]
+    Bake,
+    /// Keep each element's points in its own coordinate system, wrapping
+    /// elements that have a `transform` in a scope with the equivalent `cm`
+    /// option
+    Scope,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            flip_y: true,
+            map_viewport: true,
+            scale_to: None,
+            transform_mode: TransformMode::Bake,
+            group_scopes: false,
+            recolor: None,
+            variant: None,
+            source_variant: None,
+            aws_palette: false,
+        }
+    }
+}
+
+/// Parses a whitespace and/or comma separated list of numbers, as used by
+/// `viewBox`, `points` and friends. Like path data, numbers needn't be
+/// separated where a sign or second decimal point makes the split clear,
+/// e.g. `10-5.5.5` is `10 -5.5 .5`.
+fn parse_numbers(s: &str) -> Option<Vec<f32>> {
+    let bytes = s.as_bytes();
+    let mut numbers = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b',') {
+            i += 1;
+        }
+        if i == bytes.len() {
+            return Some(numbers);
+        }
+        let start = i;
+        if matches!(bytes[i], b'+' | b'-') {
+            i += 1;
+        }
+        let digits = |i: &mut usize| {
+            let from = *i;
+            while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+                *i += 1;
+            }
+            *i > from
+        };
+        let mut mantissa = digits(&mut i);
+        if i < bytes.len() && bytes[i] == b'.' {
+            i += 1;
+            mantissa |= digits(&mut i);
+        }
+        if !mantissa {
+            return None;
+        }
+        if i < bytes.len() && matches!(bytes[i], b'e' | b'E') {
+            i += 1;
+            if i < bytes.len() && matches!(bytes[i], b'+' | b'-') {
+                i += 1;
+            }
+            if !digits(&mut i) {
+                return None;
+            }
+        }
+        numbers.push(s[start..i].parse().ok()?);
     }
 }
 
-pub fn parse_svg<R: Read>(input: R) -> anyhow::Result<TikzDraw> {
-    let mut result = TikzDraw::default();
+/// Parses a length such as `height="48px"` into user units (px). Fonts
+/// aren't converted, so `em` and `ex` are of the default font size of 16px.
+/// Percentages give `None`, see `Viewport::length` for those.
+fn parse_length(s: &str) -> Option<f32> {
+    let s = s.trim();
+    let number_end = s
+        .rfind(|c: char| c.is_ascii_digit() || c == '.')
+        .map_or(0, |i| i + 1);
+    let (number, unit) = s.split_at(number_end);
+    let scale = match unit {
+        "" | "px" => 1.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "em" => 16.0,
+        "ex" => 8.0,
+        _ => return None,
+    };
+    number.parse::<f32>().ok().map(|n| n * scale)
+}
+
+pub fn parse_svg<R: Read>(input: R) -> Result<TikzPicture, ConversionError> {
+    parse_svg_with(input, &Options::default())
+}
+
+pub fn parse_svg_with<R: Read>(
+    input: R,
+    options: &Options,
+) -> Result<TikzPicture, ConversionError> {
     let input = std::io::read_to_string(input)?;
-    // for now, just add the same attributes every time
-    result.attributes.push(Attribute::setting("fill"));
-    result.attributes.push(Attribute::setting("even odd rule"));
-    result.attributes.push(Attribute::param("line width", "1"));
+    let mut root = dom::parse(&input)?;
+    css::apply(&mut root);
+    if root.name != "svg" {
+        return Err(ConversionError::UnsupportedElement {
+            element: root.reference(),
+        });
+    }
+    let root_transform = viewport::root_transform(&root, options)?;
+    let root_viewport = viewport::root_viewport(&root)?;
+    let title = variant::title(&root);
+    let source_variant = title.and_then(Variant::detect).or(options.source_variant);
+    let converter = Converter {
+        options,
+        root: &root,
+        ids: reference::collect_ids(&root),
+        expanding: RefCell::default(),
+        colors: RefCell::new(ColorTable::new(options, title, source_variant)),
+        shadings: RefCell::default(),
+    };
+    let context = Context {
+        ctm: root_transform,
+        canvas: root_transform,
+        viewport: root_viewport,
+        style: Style::initial(),
+    };
+    let items = match options.transform_mode {
+        TransformMode::Bake => converter.convert(&root, &context)?,
+        TransformMode::Scope => {
+            let context = Context {
+                ctm: Transform::identity(),
+                ..context
+            };
+            let items = converter.convert(&root, &context)?;
+            vec![TikzItem::Scope(TikzScope {
+                attributes: vec![root_transform.to_attribute()],
+                comment: None,
+                items,
+            })]
+        }
+    };
+    Ok(TikzPicture {
+        colors: converter.colors.into_inner(),
+        shadings: converter.shadings.into_inner(),
+        variant: options.variant.or(source_variant),
+        items,
+    })
+}
+
+/// The state an element inherits from its ancestors
+#[derive(Debug, Clone)]
+struct Context {
+    /// The transformation from the element's user space to tikz coordinates
+    ctm: Transform,
+    /// The same as `ctm`, but including the transformations kept as scopes,
+    /// since tikz doesn't apply those to line widths or shadings
+    canvas: Transform,
+    /// The viewport percentage lengths refer to
+    viewport: Viewport,
+    /// The element's computed style
+    style: Style,
+}
+
+/// Walks the SVG element tree, turning it into tikz items
+struct Converter<'a> {
+    options: &'a Options,
+    /// The root `<svg>` element, whose viewport `viewport::root_transform`
+    /// maps already, unlike those of nested ones
+    root: &'a Element<'a>,
+    /// Every element in the document with an id, for resolving references
+    ids: HashMap<&'a str, &'a Element<'a>>,
+    /// The ids of the elements currently being instantiated by a `<use>`,
+    /// innermost last
+    expanding: RefCell<Vec<&'a str>>,
+    /// The colours used so far
+    colors: RefCell<ColorTable>,
+    /// The gradients used so far, as shadings
+    shadings: RefCell<ShadingTable>,
+}
+
+impl<'a> Converter<'a> {
+    /// Converts `element` and everything in it, given the context of its
+    /// parent
+    fn convert(
+        &self,
+        element: &'a Element<'a>,
+        parent: &Context,
+    ) -> Result<Vec<TikzItem>, ConversionError> {
+        let mut local = match element.attr("transform") {
+            Some(value) => Transform::parse(value).ok_or_else(|| element.malformed("transform"))?,
+            None => Transform::identity(),
+        };
+        if element.name == "use" {
+            let x = element.length("x", &parent.viewport)?.unwrap_or(0.0);
+            let y = element.length("y", &parent.viewport)?.unwrap_or(0.0);
+            local = local.compose(&Transform::translate(x, y));
+        }
+        let mut viewport = parent.viewport;
+        if element.name == "svg" && !std::ptr::eq(element, self.root) {
+            let (fit, nested) = viewport::nested_svg_transform(element, &parent.viewport)?;
+            local = local.compose(&fit);
+            viewport = nested;
+        }
+        let specified = Style::specified(element, &parent.viewport)?;
+        let context = Context {
+            ctm: match self.options.transform_mode {
+                TransformMode::Bake => parent.ctm.compose(&local),
+                TransformMode::Scope => Transform::identity(),
+            },
+            canvas: parent.canvas.compose(&local),
+            viewport,
+            style: specified.clone().inherit(&parent.style),
+        };
+
+        let items = match element.name {
+            "svg" | "g" | "a" | "switch" => {
+                let mut items = Vec::new();
+                for child in &element.children {
+                    items.extend(self.convert(child, &context)?);
+                }
+                items
+            }
+            "path" => vec![TikzItem::Draw(self.convert_path(element, &context)?)],
+            "use" => self.convert_use(element, &context)?,
+            "circle" | "ellipse" => self
+                .convert_ellipse(element, &context)?
+                .into_iter()
+                .map(TikzItem::Draw)
+                .collect(),
+            "line" | "polyline" | "polygon" => self
+                .convert_polyline(element, &context)?
+                .into_iter()
+                .map(TikzItem::Draw)
+                .collect(),
+            "rect" => self
+                .convert_rect(element, &context)?
+                .into_iter()
+                .map(TikzItem::Draw)
+                .collect(),
+            name if UNSUPPORTED_ELEMENTS.contains(&name) => {
+                return Err(ConversionError::UnsupportedElement {
+                    element: element.reference(),
+                })
+            }
+            // everything else is either not drawn directly (<defs>,
+            // <title>, ...) or not understood
+            _ => vec![],
+        };
+        if items.is_empty() {
+            return Ok(items);
+        }
+
+        // the opacity of an element drawn directly is part of its draw, but
+        // that of a container applies to its contents once they have been
+        // composited, so that overlapping children don't compound
+        let group_opacity = match element.name {
+            "svg" | "g" | "a" | "switch" | "use" => {
+                specified.opacity.filter(|&opacity| opacity < 1.0)
+            }
+            _ => None,
+        };
+        let transparency_group = |opacity: f32| {
+            [
+                Attribute::setting("transparency group"),
+                Attribute::param("opacity", format!("{:.4}", opacity)),
+            ]
+        };
+
+        if element.name == "g" && self.options.group_scopes {
+            let mut attributes = Vec::new();
+            if self.options.transform_mode == TransformMode::Scope && local != Transform::identity()
+            {
+                attributes.push(local.to_attribute());
+            }
+            if let Some(opacity) = group_opacity {
+                attributes.extend(transparency_group(opacity));
+            }
+            // currentColor refers to the computed colour, even if the group
+            // doesn't set it itself
+            let style = Style {
+                color: context.style.color,
+                opacity: None,
+                ..specified
+            };
+            attributes.extend(
+                style.attributes(context.canvas.mean_scale(), &mut self.colors.borrow_mut()),
+            );
+            return Ok(vec![TikzItem::Scope(TikzScope {
+                attributes,
+                comment: element.attr("id").map(str::to_string),
+                items,
+            })]);
+        }
+        let items = match group_opacity {
+            Some(opacity) => vec![TikzItem::Scope(TikzScope {
+                attributes: transparency_group(opacity).into(),
+                comment: None,
+                items,
+            })],
+            None => items,
+        };
+        Ok(self.scoped(&local, items))
+    }
+
+    /// In scope mode, wraps `items` in a scope applying `local`, unless
+    /// there is nothing to transform
+    fn scoped(&self, local: &Transform, items: Vec<TikzItem>) -> Vec<TikzItem> {
+        if self.options.transform_mode == TransformMode::Scope
+            && *local != Transform::identity()
+            && !items.is_empty()
+        {
+            return vec![TikzItem::Scope(TikzScope {
+                attributes: vec![local.to_attribute()],
+                comment: None,
+                items,
+            })];
+        }
+        items
+    }
 
-    for event in svg::read(&input)? {
-        use svg::node::element::tag;
-        #[allow(clippy::single_match)]
-        match event {
-            svg::parser::Event::Tag(tag::Path, _, attrs) => {
-                let data = attrs.get("d").unwrap();
-                let data = Data::parse(data).unwrap();
-                result.path_sections = data.iter().map(PathSection::from_svg).collect();
-                break;
+    /// A draw with the attributes of the element with the given `context`,
+    /// but no path yet
+    fn new_draw(&self, context: &Context) -> Result<TikzDraw, ConversionError> {
+        let gradient = match &context.style.fill {
+            Some(Paint::Url(id, _)) => self.gradient_attributes(id, context)?,
+            _ => None,
+        };
+        let mut style = context.style.clone();
+        if gradient.is_some() {
+            style.fill = None;
+        }
+        if let Some(Paint::Url(id, _)) = &context.style.stroke {
+            if let Some(paint) = self.gradient_stroke(id, context)? {
+                style.stroke = Some(paint);
             }
-            _ => {} // ignore everything esle
         }
+        let mut attributes =
+            style.attributes(context.canvas.mean_scale(), &mut self.colors.borrow_mut());
+        attributes.extend(gradient.into_iter().flatten());
+        Ok(TikzDraw {
+            attributes,
+            ..TikzDraw::default()
+        })
     }
 
-    Ok(result)
+    fn convert_path(
+        &self,
+        element: &Element,
+        context: &Context,
+    ) -> Result<TikzDraw, ConversionError> {
+        let data = element.required("d")?;
+        let mut draw = self.new_draw(context)?;
+        draw.path_sections = convert_path_data(data, element.reference())?
+            .iter()
+            .map(|section| section.transformed(&context.ctm))
+            .collect();
+        Ok(draw)
+    }
 }
 
 #[cfg(test)]
@@ -135,4 +1018,647 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_y_axis_is_flipped() -> anyhow::Result<()> {
+        let f = File::open("testfiles/lambda.svg")?;
+        let tikz = parse_svg(f)?;
+        // the lambda icon's circle starts at the bottom of its 48x48 viewBox
+        assert_eq!(
+            tikz.draws()[0].path_sections[0],
+            PathSection::Move(Point(24.0, 4.0))
+        );
+
+        let f = File::open("testfiles/lambda.svg")?;
+        let options = Options {
+            flip_y: false,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(f, &options)?;
+        assert_eq!(
+            tikz.draws()[0].path_sections[0],
+            PathSection::Move(Point(24.0, 44.0))
+        );
+
+        // the origin ends up at the bottom left of the viewBox
+        let svg = r#"<svg viewBox="0 10 20 20"><path d="M5,12 L5,28"/></svg>"#;
+        let tikz = parse_svg(svg.as_bytes())?;
+        assert_eq!(
+            tikz.draws()[0].path_sections,
+            vec![
+                PathSection::Move(Point(5.0, 18.0)),
+                PathSection::Line(Point(5.0, 2.0)),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_transforms_are_baked() -> anyhow::Result<()> {
+        let svg = r#"<svg viewBox="0 0 48 48">
+            <g transform="translate(10,0)">
+                <g transform="scale(2)">
+                    <path d="M1,1 L2,1" transform="translate(0,3)"/>
+                </g>
+                <path d="M1,1"/>
+            </g>
+            <path d="M1,1"/>
+        </svg>"#;
+        let options = Options {
+            flip_y: false,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        let draws = tikz.draws();
+        assert_eq!(
+            draws[0].path_sections,
+            vec![
+                PathSection::Move(Point(12.0, 8.0)),
+                PathSection::Line(Point(14.0, 8.0)),
+            ]
+        );
+        assert_eq!(
+            draws[1].path_sections[0],
+            PathSection::Move(Point(11.0, 1.0))
+        );
+        assert_eq!(
+            draws[2].path_sections[0],
+            PathSection::Move(Point(1.0, 1.0))
+        );
+
+        let svg = r#"<svg><g transform="spin(3)"><path d="M0,0"/></g></svg>"#;
+        assert!(matches!(
+            parse_svg(svg.as_bytes()),
+            Err(ConversionError::MalformedAttribute {
+                attribute: "transform",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_transforms_as_scopes() -> anyhow::Result<()> {
+        let svg = r#"<svg viewBox="0 0 48 48">
+            <g transform="translate(10,0)"><path d="M1,1"/></g>
+            <g transform="scale(2)"><title>nothing drawn</title></g>
+        </svg>"#;
+        let options = Options {
+            transform_mode: TransformMode::Scope,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert_eq!(
+            tikz.to_string(),
+            "\\definecolor{svg000000}{HTML}{000000}\n\
+             \\begin{scope}[cm={1.0000,0.0000,0.0000,-1.0000,(0.0000, 48.0000)}]\n\
+             \x20 \\begin{scope}[cm={1.0000,0.0000,0.0000,1.0000,(10.0000, 0.0000)}]\n\
+             \x20   \\draw[fill=svg000000,nonzero rule,draw=none] (1.0000, 1.0000) ;\n\
+             \x20 \\end{scope}\n\
+             \\end{scope}\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_colors_are_declared_once() -> anyhow::Result<()> {
+        let f = File::open("testfiles/lambda.svg")?;
+        let tikz = parse_svg(f)?.to_string();
+        assert!(tikz.starts_with("\\definecolor{svgD45B07}{HTML}{D45B07}\n"));
+        assert!(tikz.contains("\\draw[fill=svgD45B07,"));
+
+        let svg = r##"<svg color="#ED7100">
+            <path d="M0,0" fill="rgb(237,113,0)"/>
+            <path d="M0,0" fill="currentColor" stroke="DarkOrange"/>
+            <path d="M0,0" fill="none" stroke="#ed710080"/>
+        </svg>"##;
+        assert_eq!(
+            parse_svg(svg.as_bytes())?.to_string(),
+            "\\definecolor{svgED7100}{HTML}{ED7100}\n\
+             \\definecolor{svgFF8C00}{HTML}{FF8C00}\n\
+             \\draw[fill=svgED7100,nonzero rule,draw=none] (0.0000, 0.0000) ;\n\
+             \\draw[fill=svgED7100,nonzero rule,draw=svgFF8C00,line width=1.0000cm,\
+             line cap=butt,line join=miter,miter limit=4.0000,solid] (0.0000, 0.0000) ;\n\
+             \\draw[fill=none,nonzero rule,draw=svgED7100,draw opacity=0.5020,\
+             line width=1.0000cm,line cap=butt,line join=miter,miter limit=4.0000,solid] \
+             (0.0000, 0.0000) ;\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stroke_styling() -> anyhow::Result<()> {
+        let svg = r#"<svg viewBox="0 0 48 48" width="96" height="96">
+            <g stroke="black" stroke-width="2" stroke-linecap="square">
+                <path d="M0,0" stroke-linejoin="round" stroke-dasharray="1 2 3"
+                    stroke-dashoffset="0.5" stroke-miterlimit="10"/>
+                <g transform="scale(0.5)"><path d="M0,0" stroke-dasharray="0,0"/></g>
+            </g>
+        </svg>"#;
+        let draws: Vec<_> = parse_svg(svg.as_bytes())?
+            .draws()
+            .iter()
+            .map(|draw| attributes_to_tikz(&draw.attributes))
+            .collect();
+        // the viewBox doubles everything
+        assert_eq!(
+            draws,
+            vec![
+                "fill=svg000000,nonzero rule,draw=svg000000,line width=4.0000cm,\
+                 line cap=rect,line join=round,miter limit=10.0000,\
+                 dash pattern=on 2.0000cm off 4.0000cm on 6.0000cm off 2.0000cm \
+                 on 4.0000cm off 6.0000cm,dash phase=1.0000cm",
+                "fill=svg000000,nonzero rule,draw=svg000000,line width=2.0000cm,\
+                 line cap=rect,line join=miter,miter limit=4.0000,solid",
+            ]
+        );
+
+        // tikz doesn't scale line widths by a cm, so they are scaled the
+        // same whether or not the transformations are baked
+        let options = Options {
+            transform_mode: TransformMode::Scope,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert!(tikz.draws()[1]
+            .attributes
+            .iter()
+            .any(|a| a.to_string() == "line width=2.0000cm"));
+
+        let svg = r#"<svg><path d="M0,0" stroke="red" stroke-width="-1"/></svg>"#;
+        assert!(parse_svg(svg.as_bytes()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_opacity() -> anyhow::Result<()> {
+        let svg = r##"<svg>
+            <g opacity="0.5" fill-opacity="50%">
+                <path d="M0,0" fill="#FFFFFF" opacity="0.8"/>
+                <path d="M0,0" fill="#FFFFFF80" stroke="red" stroke-opacity=".25"/>
+            </g>
+        </svg>"##;
+        assert_eq!(
+            parse_svg(svg.as_bytes())?.to_string(),
+            "\\definecolor{svgFFFFFF}{HTML}{FFFFFF}\n\
+             \\definecolor{svgFF0000}{HTML}{FF0000}\n\
+             \\begin{scope}[transparency group,opacity=0.5000]\n\
+             \x20 \\draw[opacity=0.8000,fill=svgFFFFFF,fill opacity=0.4000,nonzero rule,\
+             draw=none] (0.0000, 0.0000) ;\n\
+             \x20 \\draw[fill=svgFFFFFF,fill opacity=0.2510,nonzero rule,draw=svgFF0000,\
+             draw opacity=0.2500,line width=1.0000cm,line cap=butt,line join=miter,\
+             miter limit=4.0000,solid] (0.0000, 0.0000) ;\n\
+             \\end{scope}\n"
+        );
+
+        // with group scopes, the group's own scope is the transparency group
+        let options = Options {
+            group_scopes: true,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert!(tikz
+            .to_string()
+            .contains("\\begin{scope}[transparency group,opacity=0.5000]\n"));
+        assert_eq!(tikz.to_string().matches("\\begin{scope}").count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recolor() -> anyhow::Result<()> {
+        let svg = r##"<svg color="#ED7100">
+            <path d="M0,0" fill="currentColor" stroke="#FFFFFF"/>
+            <path d="M0,0" fill="#FFFFFF" stroke="currentColor" stroke-opacity="0.5"/>
+            <path d="M0,0" fill="#232F3E"/>
+        </svg>"##;
+        let options = Options {
+            recolor: Some(Recolor {
+                colors: vec![(Color(0xFF, 0xFF, 0xFF), "aws icon bg".to_string())],
+                ..Recolor::default()
+            }),
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        let draws: Vec<_> = tikz
+            .draws()
+            .iter()
+            .map(|draw| attributes_to_tikz(&draw.attributes[..3]))
+            .collect();
+        assert_eq!(
+            draws,
+            vec![
+                "aws icon fg,nonzero rule,aws icon bg=draw",
+                "aws icon bg,nonzero rule,aws icon fg=draw",
+                "fill=svg232F3E,nonzero rule,draw=none",
+            ]
+        );
+        assert!(tikz.to_string().starts_with(
+            "\\definecolor{svgED7100}{HTML}{ED7100}\n\
+             \\definecolor{svgFFFFFF}{HTML}{FFFFFF}\n\
+             \\definecolor{svg232F3E}{HTML}{232F3E}\n\
+             \\pgfkeysifdefined{/tikz/aws icon fg/.@cmd}{}\
+             {\\tikzset{aws icon fg/.style={#1=svgED7100}}}\n\
+             \\tikzset{aws icon fg/.default=fill}\n\
+             \\pgfkeysifdefined{/tikz/aws icon bg/.@cmd}{}\
+             {\\tikzset{aws icon bg/.style={#1=svgFFFFFF}}}\n\
+             \\tikzset{aws icon bg/.default=fill}\n"
+        ));
+        assert!(tikz
+            .to_string()
+            .contains("aws icon fg=draw,draw opacity=0.5000,"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(
+            parse_numbers(" 1,2 3 ,4\n-5e1"),
+            Some(vec![1.0, 2.0, 3.0, 4.0, -50.0])
+        );
+        assert_eq!(
+            parse_numbers("10-5.5.5+1E-1"),
+            Some(vec![10.0, -5.5, 0.5, 0.1])
+        );
+        assert_eq!(parse_numbers(""), Some(vec![]));
+        assert_eq!(parse_numbers("1 2px"), None);
+        assert_eq!(parse_numbers("1 - 2"), None);
+        assert_eq!(parse_numbers("1e"), None);
+    }
+
+    #[test]
+    fn test_fill_rule_is_inherited() -> anyhow::Result<()> {
+        let f = File::open("testfiles/lambda.svg")?;
+        let tikz = parse_svg(f)?;
+        // declared on the lambda icon's <g>
+        assert!(tikz.draws()[0]
+            .attributes
+            .iter()
+            .any(|a| a.to_string() == "even odd rule"));
+
+        let svg = r#"<svg>
+            <path d="M0,0"/>
+            <g fill-rule="evenodd">
+                <g><path d="M0,0"/></g>
+                <path d="M0,0" fill-rule="nonzero"/>
+                <path d="M0,0" fill-rule="inherit"/>
+            </g>
+        </svg>"#;
+        let rules: Vec<_> = parse_svg(svg.as_bytes())?
+            .draws()
+            .iter()
+            .map(|draw| draw.attributes[1].to_string())
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                "nonzero rule",
+                "even odd rule",
+                "nonzero rule",
+                "even odd rule"
+            ]
+        );
+
+        let svg = r#"<svg><path d="M0,0" fill-rule="odd"/></svg>"#;
+        assert!(matches!(
+            parse_svg(svg.as_bytes()),
+            Err(ConversionError::MalformedAttribute {
+                attribute: "fill-rule",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_scopes() -> anyhow::Result<()> {
+        let svg = r#"<svg viewBox="0 0 10 10">
+            <g id="outer" fill-rule="evenodd">
+                <g transform="translate(1,1)"><path d="M0,0"/></g>
+                <g id="empty"/>
+                <path d="M2,2"/>
+            </g>
+        </svg>"#;
+        let options = Options {
+            group_scopes: true,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert_eq!(
+            tikz.to_string(),
+            "\\definecolor{svg000000}{HTML}{000000}\n\
+             \\begin{scope}[even odd rule] % outer\n\
+             \x20 \\begin{scope}\n\
+             \x20   \\draw[fill=svg000000,even odd rule,draw=none] (1.0000, 9.0000) ;\n\
+             \x20 \\end{scope}\n\
+             \x20 \\draw[fill=svg000000,even odd rule,draw=none] (2.0000, 8.0000) ;\n\
+             \\end{scope}\n"
+        );
+
+        // with transforms kept as scopes, groups carry their own
+        let options = Options {
+            group_scopes: true,
+            transform_mode: TransformMode::Scope,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert_eq!(tikz.to_string().matches("\\begin{scope}").count(), 3);
+        assert!(tikz
+            .to_string()
+            .contains("\\begin{scope}[cm={1.0000,0.0000,0.0000,1.0000,(1.0000, 1.0000)}]\n"));
+
+        Ok(())
+    }
+
+    fn convert(d: &str) -> Vec<PathSection> {
+        convert_path_data(d, ElementRef::default()).unwrap()
+    }
+
+    #[test]
+    fn test_every_path_is_converted() -> anyhow::Result<()> {
+        let svg = r#"<svg viewBox="0 0 64 64">
+            <g><path d="M0,0 L64,0 L64,64 L0,64 Z"/></g>
+            <path d="M10,10 L20,20"/>
+            <path d="M30,30 L40,40"></path>
+        </svg>"#;
+        let tikz = parse_svg(svg.as_bytes())?;
+        assert_eq!(tikz.draws().len(), 3);
+        assert_eq!(
+            tikz.draws()[2].path_sections,
+            vec![
+                PathSection::Move(Point(30.0, 34.0)),
+                PathSection::Line(Point(40.0, 24.0)),
+            ]
+        );
+        // the shared black fill is declared once, then a line per path
+        assert_eq!(tikz.to_string().lines().count(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        let svg = "<svg>\n  <path id=\"bad\" d=\"M0,0 L1\"/>\n</svg>";
+        match parse_svg(svg.as_bytes()) {
+            Err(ConversionError::MalformedPathData { element, .. }) => {
+                assert_eq!(element.id.as_deref(), Some("bad"));
+                assert_eq!(element.line, 2);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let svg = "<svg><path d=\"M0,0 X1,1\"/></svg>";
+        assert!(matches!(
+            parse_svg(svg.as_bytes()),
+            Err(ConversionError::UnsupportedCommand { command: 'X', .. })
+        ));
+
+        let svg = "<svg><path id=\"empty\"/></svg>";
+        assert!(matches!(
+            parse_svg(svg.as_bytes()),
+            Err(ConversionError::MissingAttribute { attribute: "d", .. })
+        ));
+
+        let svg = "<svg><text>hi</text><path d=\"M0,0\"/></svg>";
+        let err = parse_svg(svg.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "<text> on line 1 cannot be converted");
+    }
+
+    #[test]
+    fn test_relative_commands() {
+        let sections = convert("m10,10 l5,0 0,5 c1,1 2,2 3,3 z m2,2 l1,1");
+        assert_eq!(
+            sections,
+            vec![
+                PathSection::Move(Point(10.0, 10.0)),
+                PathSection::Line(Point(15.0, 10.0)),
+                PathSection::Line(Point(15.0, 15.0)),
+                PathSection::Curve(Point(16.0, 16.0), Point(17.0, 17.0), Point(18.0, 18.0)),
+                PathSection::Cycle,
+                PathSection::Move(Point(12.0, 12.0)),
+                PathSection::Line(Point(13.0, 13.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_implicit_lineto_after_move() {
+        let sections = convert("M1,1 2,2 m1,0 1,0");
+        assert_eq!(
+            sections,
+            vec![
+                PathSection::Move(Point(1.0, 1.0)),
+                PathSection::Line(Point(2.0, 2.0)),
+                PathSection::Move(Point(3.0, 2.0)),
+                PathSection::Line(Point(4.0, 2.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drawing_on_after_close() {
+        assert_eq!(
+            convert("M0,0 L10,0 L10,10 Z L0,10 z Z h5"),
+            vec![
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Line(Point(10.0, 0.0)),
+                PathSection::Line(Point(10.0, 10.0)),
+                PathSection::Cycle,
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Line(Point(0.0, 10.0)),
+                PathSection::Cycle,
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Line(Point(5.0, 0.0)),
+            ]
+        );
+        assert!(convert_path_data("L1,1 Z", ElementRef::default()).is_err());
+    }
+
+    #[test]
+    fn test_relative_commands_after_close_in_lambda_icon() {
+        // the first two subpaths of the lambda glyph in testfiles/lambda.svg
+        // as given, and rewritten the way svgo would, with relative commands
+        // following each `z`
+        let absolute = "M17.231,35.25 L11.876,35.25 L18.221,21.959 L20.902,27.492 \
+            L17.231,35.25 Z M19.114,19.215 C18.946,18.87 18.597,18.651 18.214,18.651 \
+            L18.211,18.651 Z";
+        let relative = "M17.231,35.25 l-5.355,0 6.345,-13.291 2.681,5.533 -3.671,7.758 z \
+            m1.883,-16.035 c-.168,-.345 -.517,-.564 -.9,-.564 l-.003,0 z";
+        let expected = convert(absolute);
+        assert_sections_close(&convert(relative), &expected);
+
+        // and the same with the second moveto dropped: the glyph then carries
+        // on from where the first subpath started
+        let implicit = "M17.231,35.25 l-5.355,0 6.345,-13.291 2.681,5.533 -3.671,7.758 z \
+            c1.715,-16.38 1.366,-16.599 .983,-16.599";
+        let sections = convert(implicit);
+        assert_eq!(sections[6], PathSection::Move(Point(17.231, 35.25)));
+        assert_sections_close(
+            &sections[7..],
+            &[PathSection::Curve(
+                Point(18.946, 18.87),
+                Point(18.597, 18.651),
+                Point(18.214, 18.651),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_horizontal_and_vertical_lines() {
+        let sections = convert("M2,2 H10 V6 L4,8 h-2 v-3 1 H1 3");
+        assert_eq!(
+            sections,
+            vec![
+                PathSection::Move(Point(2.0, 2.0)),
+                PathSection::Line(Point(10.0, 2.0)),
+                PathSection::Line(Point(10.0, 6.0)),
+                PathSection::Line(Point(4.0, 8.0)),
+                PathSection::Line(Point(2.0, 8.0)),
+                PathSection::Line(Point(2.0, 5.0)),
+                PathSection::Line(Point(2.0, 6.0)),
+                PathSection::Line(Point(1.0, 6.0)),
+                PathSection::Line(Point(3.0, 6.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_smooth_cubic_curves() {
+        let sections = convert("M0,0 C1,2 3,2 4,0 S7,-2 8,0 s3,2 4,0");
+        assert_eq!(
+            sections,
+            vec![
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Curve(Point(1.0, 2.0), Point(3.0, 2.0), Point(4.0, 0.0)),
+                PathSection::Curve(Point(5.0, -2.0), Point(7.0, -2.0), Point(8.0, 0.0)),
+                PathSection::Curve(Point(9.0, 2.0), Point(11.0, 2.0), Point(12.0, 0.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_smooth_cubic_without_previous_curve() {
+        // with no preceding C/S the first control point is the current point
+        let sections = convert("M0,0 L2,0 S3,1 4,0");
+        assert_eq!(
+            sections[2],
+            PathSection::Curve(Point(2.0, 0.0), Point(3.0, 1.0), Point(4.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_quadratic_curves() {
+        let sections = convert("M0,0 Q3,3 6,0 T12,0 t6,0");
+        assert_eq!(
+            sections,
+            vec![
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Curve(Point(2.0, 2.0), Point(4.0, 2.0), Point(6.0, 0.0)),
+                // reflected control point is (9, -3)
+                PathSection::Curve(Point(8.0, -2.0), Point(10.0, -2.0), Point(12.0, 0.0)),
+                // reflected control point is (15, 3)
+                PathSection::Curve(Point(14.0, 2.0), Point(16.0, 2.0), Point(18.0, 0.0)),
+            ]
+        );
+    }
+
+    fn assert_sections_close(actual: &[PathSection], expected: &[PathSection]) {
+        fn close(a: &Point, b: &Point) -> bool {
+            (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+        }
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            let matches = match (a, e) {
+                (PathSection::Move(a), PathSection::Move(e)) => close(a, e),
+                (PathSection::Line(a), PathSection::Line(e)) => close(a, e),
+                (PathSection::Curve(a1, a2, a3), PathSection::Curve(e1, e2, e3)) => {
+                    close(a1, e1) && close(a2, e2) && close(a3, e3)
+                }
+                (PathSection::Cycle, PathSection::Cycle) => true,
+                _ => false,
+            };
+            assert!(matches, "{:?} != {:?}", a, e);
+        }
+    }
+
+    #[test]
+    fn test_quarter_circle_arc() {
+        let k = 10.0 * 4.0 / 3.0 * (std::f32::consts::PI / 8.0).tan();
+        assert_sections_close(
+            &convert("M10,0 A10,10 0 0,1 0,10"),
+            &[
+                PathSection::Move(Point(10.0, 0.0)),
+                PathSection::Curve(Point(10.0, k), Point(k, 10.0), Point(0.0, 10.0)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_arc_flags_select_the_arc() {
+        // same end points, the large arc going the other way round is three
+        // quarters of the circle, ending on the quarter from (-10, 0)
+        let sections = convert("M10,0 A10,10 0 1,0 0,10");
+        assert_eq!(sections.len(), 4);
+        assert_sections_close(
+            &sections[3..],
+            &[PathSection::Curve(
+                Point(-10.0, 5.5228),
+                Point(-5.5228, 10.0),
+                Point(0.0, 10.0),
+            )],
+        );
+        // relative arc with radii too small to span the chord: a half circle
+        let sections = convert("M0,0 a1,1 0 0,1 20,0");
+        assert_eq!(sections.len(), 3);
+        assert_sections_close(
+            &sections[1..2],
+            &[PathSection::Curve(
+                Point(0.0, -5.5228),
+                Point(10.0 - 5.5228, -10.0),
+                Point(10.0, -10.0),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_rotated_arc() {
+        // a quarter turn of the ellipse axes is the same as swapping radii
+        assert_sections_close(
+            &convert("M0,0 A20,10 90 0,1 10,20"),
+            &convert("M0,0 A10,20 0 0,1 10,20"),
+        );
+    }
+
+    #[test]
+    fn test_degenerate_arcs() {
+        assert_eq!(
+            convert("M0,0 A0,5 0 0,1 10,0 A5,5 0 0,1 10,0"),
+            vec![
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Line(Point(10.0, 0.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_smooth_quadratic_after_cubic_is_a_line() {
+        // T only reflects a Q/T control point, so after a C it degenerates
+        let sections = convert("M0,0 C1,1 2,1 3,0 T6,0");
+        assert_eq!(
+            sections[2],
+            PathSection::Curve(Point(3.0, 0.0), Point(4.0, 0.0), Point(6.0, 0.0))
+        );
+        let sections = convert("M0,0 Q1,1 2,0 L3,0 T6,0");
+        assert_eq!(
+            sections[3],
+            PathSection::Curve(Point(3.0, 0.0), Point(4.0, 0.0), Point(6.0, 0.0))
+        );
+    }
 }
@@ -0,0 +1,182 @@
+//! The colours of the AWS architecture icon categories, which icons can be
+//! drawn in by name so that a document can adjust a whole category at once
+
+use crate::color::Color;
+
+/// Each category's colour name, the name the category goes by in icon
+/// titles (`Icon-Resource/<category>/...`), the colour of its service icon
+/// tiles and the (sometimes darker) colour Light resource icons draw their
+/// glyphs in
+const PALETTE: &[(&str, &str, Color, Color)] = &[
+    (
+        "awscompute",
+        "Compute",
+        Color(0xED, 0x71, 0x00),
+        Color(0xD4, 0x5B, 0x07),
+    ),
+    (
+        "awscontainers",
+        "Containers",
+        Color(0xED, 0x71, 0x00),
+        Color(0xD4, 0x5B, 0x07),
+    ),
+    (
+        "awsstorage",
+        "Storage",
+        Color(0x7A, 0xA1, 0x16),
+        Color(0x3F, 0x86, 0x24),
+    ),
+    (
+        "awsdatabase",
+        "Database",
+        Color(0x3B, 0x48, 0xCC),
+        Color(0xC9, 0x25, 0xD1),
+    ),
+    (
+        "awsnetworking",
+        "Networking-Content-Delivery",
+        Color(0x8C, 0x4F, 0xFF),
+        Color(0x8C, 0x4F, 0xFF),
+    ),
+    (
+        "awsanalytics",
+        "Analytics",
+        Color(0x8C, 0x4F, 0xFF),
+        Color(0x8C, 0x4F, 0xFF),
+    ),
+    (
+        "awssecurity",
+        "Security-Identity-Compliance",
+        Color(0xDD, 0x34, 0x4C),
+        Color(0xDD, 0x34, 0x4C),
+    ),
+    (
+        "awsappintegration",
+        "App-Integration",
+        Color(0xE7, 0x15, 0x7B),
+        Color(0xE7, 0x15, 0x7B),
+    ),
+    (
+        "awsmanagement",
+        "Management-Governance",
+        Color(0xE7, 0x15, 0x7B),
+        Color(0xE7, 0x15, 0x7B),
+    ),
+    (
+        "awsml",
+        "Machine-Learning",
+        Color(0x01, 0xA8, 0x8D),
+        Color(0x01, 0xA8, 0x8D),
+    ),
+    (
+        "awsiot",
+        "IoT",
+        Color(0x7A, 0xA1, 0x16),
+        Color(0x3F, 0x86, 0x24),
+    ),
+    (
+        "awsgeneral",
+        "General-Icons",
+        Color(0x23, 0x2F, 0x3E),
+        Color(0x23, 0x2F, 0x3E),
+    ),
+];
+
+/// The category an icon belongs to, from its title
+pub(crate) fn category(title: &str) -> Option<&str> {
+    title.split('/').nth(1)
+}
+
+/// The colour Light resource icons of `category` draw their glyphs in
+pub(crate) fn foreground(category: &str) -> Option<Color> {
+    PALETTE
+        .iter()
+        .find(|(_, c, _, _)| *c == category)
+        .map(|&(_, _, _, foreground)| foreground)
+}
+
+/// The glyph colours of Light resource icons, of every category
+pub(crate) fn foregrounds() -> impl Iterator<Item = Color> {
+    PALETTE.iter().map(|&(_, _, _, foreground)| foreground)
+}
+
+/// The palette name of `color`: a category's colour name for its tile
+/// colour, and that name with `fg` appended for a glyph colour of its own
+/// (`awscomputefg`). Where categories share a colour, that of the icon's
+/// own `category` is preferred.
+pub(crate) fn name(color: Color, category: Option<&str>) -> Option<String> {
+    let mut matches = PALETTE
+        .iter()
+        .filter_map(|&(name, c, tile, foreground)| {
+            if color == tile {
+                Some((c, name.to_string()))
+            } else if color == foreground {
+                Some((c, format!("{}fg", name)))
+            } else {
+                None
+            }
+        })
+        .peekable();
+    let first = matches.peek().map(|(_, name)| name.clone());
+    matches
+        .find(|(c, _)| Some(*c) == category)
+        .map(|(_, name)| name)
+        .or(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_svg_with, Options};
+
+    #[test]
+    fn test_palette_names() {
+        let name = |color, category| name(color, category).unwrap_or_default();
+        let orange = Color(0xED, 0x71, 0x00);
+        assert_eq!(name(orange, None), "awscompute");
+        assert_eq!(name(orange, Some("Containers")), "awscontainers");
+        assert_eq!(name(orange, Some("Storage")), "awscompute");
+        assert_eq!(name(Color(0x3B, 0x48, 0xCC), None), "awsdatabase");
+        // glyph colours of their own have names of their own
+        assert_eq!(name(Color(0xC9, 0x25, 0xD1), None), "awsdatabasefg");
+        assert_eq!(name(Color(0xD4, 0x5B, 0x07), None), "awscomputefg");
+        assert_eq!(name(Color(0x3F, 0x86, 0x24), Some("IoT")), "awsiotfg");
+        assert_eq!(
+            name(Color(0x8C, 0x4F, 0xFF), Some("Analytics")),
+            "awsanalytics"
+        );
+        assert_eq!(name(Color(0xD4, 0x5B, 0x08), None), "");
+        assert_eq!(
+            category("Icon-Resource/Compute/Res_Amazon-Lambda_Lambda-Function_48_Light"),
+            Some("Compute")
+        );
+    }
+
+    #[test]
+    fn test_palette_colors() -> anyhow::Result<()> {
+        let svg = r##"<svg><title>Icon-Resource/IoT/Res_AWS-IoT-Thing_48_Light</title>
+            <path d="M0,0" fill="#7aa116"/>
+            <path d="M0,0" fill="#ED7100" stroke="#000000"/>
+            <path d="M0,0" fill="#3F8624"/>
+        </svg>"##;
+        let options = Options {
+            aws_palette: true,
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        // palette colours can be set by the document beforehand
+        assert_eq!(
+            tikz.to_string(),
+            "\\providecolor{awsiot}{HTML}{7AA116}\n\
+             \\providecolor{awscompute}{HTML}{ED7100}\n\
+             \\definecolor{svg000000}{HTML}{000000}\n\
+             \\providecolor{awsiotfg}{HTML}{3F8624}\n\
+             \\draw[fill=awsiot,nonzero rule,draw=none] (0.0000, 0.0000) ;\n\
+             \\draw[fill=awscompute,nonzero rule,draw=svg000000,line width=1.0000cm,\
+             line cap=butt,line join=miter,miter limit=4.0000,solid] (0.0000, 0.0000) ;\n\
+             \\draw[fill=awsiotfg,nonzero rule,draw=none] (0.0000, 0.0000) ;\n"
+        );
+
+        Ok(())
+    }
+}
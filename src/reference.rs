@@ -0,0 +1,193 @@
+//! Instantiation of `<use>` references to elements elsewhere in the document
+
+use std::collections::HashMap;
+
+use crate::dom::Element;
+use crate::style::Style;
+use crate::{viewport, Context, ConversionError, Converter, TikzItem, Transform, TransformMode};
+
+/// Indexes every element under `root` (inclusive) by its id. Should an id
+/// be used twice, the first element wins.
+pub(crate) fn collect_ids<'a>(root: &'a Element<'a>) -> HashMap<&'a str, &'a Element<'a>> {
+    fn collect<'a>(element: &'a Element<'a>, ids: &mut HashMap<&'a str, &'a Element<'a>>) {
+        if let Some(id) = element.attr("id") {
+            ids.entry(id).or_insert(element);
+        }
+        for child in &element.children {
+            collect(child, ids);
+        }
+    }
+    let mut ids = HashMap::new();
+    collect(root, &mut ids);
+    ids
+}
+
+impl<'a> Converter<'a> {
+    /// Converts the element a `<use>` refers to, as if it were a child of the
+    /// `<use>`. The `<use>`'s `context` already includes its own
+    /// transformation and x/y offset.
+    pub(crate) fn convert_use(
+        &self,
+        element: &'a Element<'a>,
+        context: &Context,
+    ) -> Result<Vec<TikzItem>, ConversionError> {
+        // SVG 2 dropped the xlink namespace, but most tools still write it
+        let attribute = match element.attr("href") {
+            Some(_) => "href",
+            None => "xlink:href",
+        };
+        let href = element.required(attribute)?;
+        // only same-document references can be resolved
+        let id = href
+            .strip_prefix('#')
+            .ok_or_else(|| element.malformed(attribute))?;
+        let target = *self
+            .ids
+            .get(id)
+            .ok_or_else(|| ConversionError::UnresolvedReference {
+                element: element.reference(),
+                id: id.to_string(),
+            })?;
+        if self.expanding.borrow().contains(&id) {
+            return Err(ConversionError::CircularReference {
+                element: element.reference(),
+                id: id.to_string(),
+            });
+        }
+
+        self.expanding.borrow_mut().push(id);
+        let items = match target.name {
+            "symbol" => self.convert_symbol(target, element, context),
+            _ => self.convert(target, context),
+        };
+        self.expanding.borrow_mut().pop();
+        items
+    }
+
+    /// Converts the content of a `<symbol>` instantiated by `instance`,
+    /// fitting the symbol's viewBox into the size the `<use>` gives it
+    fn convert_symbol(
+        &self,
+        symbol: &'a Element<'a>,
+        instance: &Element,
+        parent: &Context,
+    ) -> Result<Vec<TikzItem>, ConversionError> {
+        let (local, viewport) = viewport::symbol_transform(
+            symbol,
+            instance.length("width", &parent.viewport)?,
+            instance.length("height", &parent.viewport)?,
+            &parent.viewport,
+        )?;
+        let context = Context {
+            ctm: match self.options.transform_mode {
+                TransformMode::Bake => parent.ctm.compose(&local),
+                TransformMode::Scope => Transform::identity(),
+            },
+            canvas: parent.canvas.compose(&local),
+            viewport,
+            style: Style::specified(symbol, &parent.viewport)?.inherit(&parent.style),
+        };
+        let mut items = Vec::new();
+        for child in &symbol.children {
+            items.extend(self.convert(child, &context)?);
+        }
+        Ok(self.scoped(&local, items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_svg, parse_svg_with, ConversionError, Options, PathSection, Point};
+
+    fn options() -> Options {
+        Options {
+            flip_y: false,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn test_use_instantiates_defs() -> anyhow::Result<()> {
+        let svg = r##"<svg xmlns:xlink="http://www.w3.org/1999/xlink">
+            <defs>
+                <path id="dot" d="M1,1 L2,2"/>
+                <g id="pair" transform="translate(10)">
+                    <use xlink:href="#dot"/>
+                    <use href="#dot" x="5" y="5"/>
+                </g>
+            </defs>
+            <use xlink:href="#dot" x="3" transform="scale(2)"/>
+            <use href="#pair" y="100"/>
+        </svg>"##;
+        let tikz = parse_svg_with(svg.as_bytes(), &options())?;
+        let starts: Vec<_> = tikz
+            .draws()
+            .iter()
+            .map(|draw| draw.path_sections[0].clone())
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                PathSection::Move(Point(8.0, 2.0)),
+                PathSection::Move(Point(11.0, 101.0)),
+                PathSection::Move(Point(16.0, 106.0)),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_use_of_symbol_fits_its_view_box() -> anyhow::Result<()> {
+        let svg = r##"<svg>
+            <symbol id="icon" viewBox="0 0 10 10"><rect width="10" height="10"/></symbol>
+            <use href="#icon" x="5" width="20" height="20"/>
+            <use href="#icon"/>
+        </svg>"##;
+        let tikz = parse_svg_with(svg.as_bytes(), &options())?;
+        let draws = tikz.draws();
+        assert_eq!(draws.len(), 2);
+        assert_eq!(
+            draws[0].path_sections,
+            vec![PathSection::Rectangle(Point(5.0, 0.0), Point(25.0, 20.0))]
+        );
+        assert_eq!(
+            draws[1].path_sections,
+            vec![PathSection::Rectangle(Point(0.0, 0.0), Point(10.0, 10.0))]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_references() {
+        let svg = r##"<svg><use href="#nowhere"/></svg>"##;
+        assert!(matches!(
+            parse_svg(svg.as_bytes()),
+            Err(ConversionError::UnresolvedReference { id, .. }) if id == "nowhere"
+        ));
+
+        let svg = r##"<svg><use href="other.svg#icon"/></svg>"##;
+        assert!(matches!(
+            parse_svg(svg.as_bytes()),
+            Err(ConversionError::MalformedAttribute {
+                attribute: "href",
+                ..
+            })
+        ));
+
+        let svg = r##"<svg>
+            <g id="a"><use href="#b"/></g>
+            <g id="b"><path d="M0,0"/><use href="#a"/></g>
+        </svg>"##;
+        match parse_svg(svg.as_bytes()) {
+            Err(ConversionError::CircularReference { element, id }) => {
+                // a -> b -> a -> b, caught at the second visit to the first
+                // <use>
+                assert_eq!(id, "b");
+                assert_eq!(element.line, 2);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Conversion of the SVG basic shapes into tikz paths
+
+use crate::dom::Element;
+use crate::viewport::Viewport;
+use crate::{
+    convert_path_data, parse_numbers, Attribute, Context, ConversionError, Converter, PathSection,
+    Point, TikzDraw,
+};
+
+/// The `rx` and `ry` of a `<rect>` or `<ellipse>`, where a missing (or
+/// `auto`) radius takes the value of the other one
+fn radii(element: &Element, viewport: &Viewport) -> Result<(f32, f32), ConversionError> {
+    let radius = |attribute| match element.attr(attribute) {
+        Some("auto") | None => Ok(None),
+        Some(_) => element.length(attribute, viewport),
+    };
+    Ok(match (radius("rx")?, radius("ry")?) {
+        (None, None) => (0.0, 0.0),
+        (Some(r), None) | (None, Some(r)) => (r, r),
+        (Some(rx), Some(ry)) => (rx, ry),
+    })
+}
+
+impl Converter<'_> {
+    /// Converts a `<rect>`, or gives `None` if it has no area and so isn't
+    /// rendered. Where the corners are round, it becomes a tikz `rectangle`
+    /// with `rounded corners` if they stay circular, and an explicit path
+    /// otherwise.
+    pub(crate) fn convert_rect(
+        &self,
+        element: &Element,
+        context: &Context,
+    ) -> Result<Option<TikzDraw>, ConversionError> {
+        let (ctm, viewport) = (&context.ctm, &context.viewport);
+        let x = element.length("x", viewport)?.unwrap_or(0.0);
+        let y = element.length("y", viewport)?.unwrap_or(0.0);
+        let width = element.length("width", viewport)?.unwrap_or(0.0);
+        let height = element.length("height", viewport)?.unwrap_or(0.0);
+        if width <= 0.0 || height <= 0.0 {
+            return Ok(None);
+        }
+        let (rx, ry) = radii(element, viewport)?;
+        let rx = rx.clamp(0.0, width / 2.0);
+        let ry = ry.clamp(0.0, height / 2.0);
+
+        let mut draw = self.new_draw(context)?;
+        // tikz doesn't transform the radius of rounded corners, so the
+        // corners are measured on the canvas, scopes included
+        let canvas = &context.canvas;
+        let (sx, sy) = (canvas.a.abs(), canvas.d.abs());
+        if ctm.is_axis_aligned()
+            && canvas.is_axis_aligned()
+            && (rx == 0.0 || ry == 0.0 || rx * sx == ry * sy)
+        {
+            if rx > 0.0 && ry > 0.0 {
+                draw.attributes.push(Attribute::param(
+                    "rounded corners",
+                    format!("{:.4}cm", rx * sx),
+                ));
+            }
+            draw.path_sections.push(PathSection::Rectangle(
+                ctm.apply(Point(x, y)),
+                ctm.apply(Point(x + width, y + height)),
+            ));
+        } else {
+            // trace the outline the way the SVG spec defines it, with an
+            // elliptical arc for each corner
+            let (right, bottom) = (x + width, y + height);
+            let d = if rx > 0.0 && ry > 0.0 {
+                format!(
+                    "M{},{} H{} A{rx},{ry} 0 0 1 {},{} V{} A{rx},{ry} 0 0 1 {},{} \
+                     H{} A{rx},{ry} 0 0 1 {},{} V{} A{rx},{ry} 0 0 1 {},{} Z",
+                    x + rx,
+                    y,
+                    right - rx,
+                    right,
+                    y + ry,
+                    bottom - ry,
+                    right - rx,
+                    bottom,
+                    x + rx,
+                    x,
+                    bottom - ry,
+                    y + ry,
+                    x + rx,
+                    y,
+                    rx = rx,
+                    ry = ry,
+                )
+            } else {
+                format!("M{},{} H{} V{} H{} Z", x, y, right, bottom, x)
+            };
+            draw.path_sections = convert_path_data(&d, element.reference())?
+                .iter()
+                .map(|section| section.transformed(ctm))
+                .collect();
+        }
+        Ok(Some(draw))
+    }
+
+    /// Converts a `<line>`, `<polyline>` or `<polygon>` into straight path
+    /// sections, closing the path for a polygon. Gives `None` if there are
+    /// no points to draw.
+    pub(crate) fn convert_polyline(
+        &self,
+        element: &Element,
+        context: &Context,
+    ) -> Result<Option<TikzDraw>, ConversionError> {
+        let (ctm, viewport) = (&context.ctm, &context.viewport);
+        let points = if element.name == "line" {
+            let coordinate = |attribute| {
+                element
+                    .length(attribute, viewport)
+                    .map(|c| c.unwrap_or(0.0))
+            };
+            vec![
+                Point(coordinate("x1")?, coordinate("y1")?),
+                Point(coordinate("x2")?, coordinate("y2")?),
+            ]
+        } else {
+            let numbers = match element.attr("points") {
+                Some(points) => parse_numbers(points)
+                    // like malformed path data, an odd coordinate out is an
+                    // error
+                    .filter(|numbers| numbers.len() % 2 == 0)
+                    .ok_or_else(|| element.malformed("points"))?,
+                None => vec![],
+            };
+            numbers.chunks_exact(2).map(|p| Point(p[0], p[1])).collect()
+        };
+        let (first, rest) = match points.split_first() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+
+        let mut draw = self.new_draw(context)?;
+        draw.path_sections
+            .push(PathSection::Move(ctm.apply(*first)));
+        draw.path_sections
+            .extend(rest.iter().map(|p| PathSection::Line(ctm.apply(*p))));
+        if element.name == "polygon" {
+            draw.path_sections.push(PathSection::Cycle);
+        }
+        Ok(Some(draw))
+    }
+
+    /// Converts a `<circle>` or `<ellipse>`, or gives `None` if it has no
+    /// area. It stays a native tikz `circle` or `ellipse` as long as the
+    /// transformation allows it, and is traced with curves otherwise.
+    pub(crate) fn convert_ellipse(
+        &self,
+        element: &Element,
+        context: &Context,
+    ) -> Result<Option<TikzDraw>, ConversionError> {
+        let (ctm, viewport) = (&context.ctm, &context.viewport);
+        let cx = element.length("cx", viewport)?.unwrap_or(0.0);
+        let cy = element.length("cy", viewport)?.unwrap_or(0.0);
+        let (rx, ry) = if element.name == "circle" {
+            let r = element.length("r", viewport)?.unwrap_or(0.0);
+            (r, r)
+        } else {
+            radii(element, viewport)?
+        };
+        if rx <= 0.0 || ry <= 0.0 {
+            return Ok(None);
+        }
+
+        let mut draw = self.new_draw(context)?;
+        let center = Point(cx, cy);
+        let section = match ctm.uniform_scale() {
+            Some(scale) if rx == ry => Some(PathSection::Circle(ctm.apply(center), rx * scale)),
+            _ if ctm.is_axis_aligned() => {
+                let (rx, ry) = (rx * ctm.a.abs(), ry * ctm.d.abs());
+                Some(if rx == ry {
+                    PathSection::Circle(ctm.apply(center), rx)
+                } else {
+                    PathSection::Ellipse(ctm.apply(center), rx, ry)
+                })
+            }
+            _ => None,
+        };
+        match section {
+            Some(section) => draw.path_sections.push(section),
+            None => {
+                let d = format!(
+                    "M{},{} A{rx},{ry} 0 1 1 {},{} A{rx},{ry} 0 1 1 {},{} Z",
+                    cx + rx,
+                    cy,
+                    cx - rx,
+                    cy,
+                    cx + rx,
+                    cy,
+                    rx = rx,
+                    ry = ry,
+                );
+                draw.path_sections = convert_path_data(&d, element.reference())?
+                    .iter()
+                    .map(|section| section.transformed(ctm))
+                    .collect();
+            }
+        }
+        Ok(Some(draw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_svg_with, Options, PathSection, Point, TikzPicture, TransformMode};
+
+    fn convert(svg: &str) -> TikzPicture {
+        let options = Options {
+            flip_y: false,
+            ..Options::default()
+        };
+        parse_svg_with(svg.as_bytes(), &options).unwrap()
+    }
+
+    #[test]
+    fn test_rect() {
+        let tikz = convert(
+            r#"<svg><rect x="1" y="2" width="10" height="5"/>
+            <rect width="0" height="5"/></svg>"#,
+        );
+        let draws = tikz.draws();
+        assert_eq!(draws.len(), 1);
+        assert_eq!(
+            draws[0].path_sections,
+            vec![PathSection::Rectangle(Point(1.0, 2.0), Point(11.0, 7.0))]
+        );
+    }
+
+    #[test]
+    fn test_rounded_rect() {
+        // ry defaults to rx
+        let tikz = convert(
+            r#"<svg><g transform="scale(2)"><rect width="10" height="8" rx="3"/></g></svg>"#,
+        );
+        assert_eq!(
+            tikz.to_string(),
+            "\\definecolor{svg000000}{HTML}{000000}\n\
+             \\draw[fill=svg000000,nonzero rule,draw=none,rounded corners=6.0000cm] \
+             (0.0000, 0.0000) rectangle (20.0000, 16.0000) ;\n"
+        );
+        // radii are clamped to half the size
+        let tikz = convert(r#"<svg><rect width="10" height="10" rx="8" ry="6"/></svg>"#);
+        assert!(tikz.to_string().contains("rounded corners=5.0000cm"));
+    }
+
+    #[test]
+    fn test_rounded_rect_in_scope_mode() {
+        let options = Options {
+            flip_y: false,
+            transform_mode: TransformMode::Scope,
+            ..Options::default()
+        };
+        let svg = r#"<svg viewBox="0 0 48 48" width="96" height="96">
+            <rect width="10" height="8" rx="3"/></svg>"#;
+        let tikz = parse_svg_with(svg.as_bytes(), &options).unwrap();
+        assert!(tikz.to_string().contains("rounded corners=6.0000cm"));
+    }
+
+    #[test]
+    fn test_elliptical_corners_become_a_path() {
+        let tikz = convert(r#"<svg><rect width="10" height="10" rx="2" ry="1"/></svg>"#);
+        let sections = &tikz.draws()[0].path_sections;
+        assert_eq!(sections[0], PathSection::Move(Point(2.0, 0.0)));
+        assert_eq!(sections[1], PathSection::Line(Point(8.0, 0.0)));
+        assert!(matches!(
+            sections[2],
+            PathSection::Curve(_, _, Point(x, y)) if x == 10.0 && y == 1.0
+        ));
+        assert_eq!(sections.last(), Some(&PathSection::Cycle));
+    }
+
+    #[test]
+    fn test_circle_and_ellipse() {
+        let tikz = convert(
+            r#"<svg><circle cx="5" cy="6" r="2"/><ellipse cx="1" cy="1" rx="3" ry="2"/>
+            <ellipse rx="3"/><circle r="0"/></svg>"#,
+        );
+        let draws = tikz.draws();
+        assert_eq!(draws.len(), 3);
+        assert_eq!(
+            draws[0].path_sections,
+            vec![PathSection::Circle(Point(5.0, 6.0), 2.0)]
+        );
+        assert_eq!(
+            draws[1].path_sections,
+            vec![PathSection::Ellipse(Point(1.0, 1.0), 3.0, 2.0)]
+        );
+        assert_eq!(
+            draws[2].path_sections,
+            vec![PathSection::Circle(Point(0.0, 0.0), 3.0)]
+        );
+        assert!(tikz
+            .to_string()
+            .contains("(1.0000, 1.0000) ellipse[x radius=3.0000,y radius=2.0000]"));
+    }
+
+    #[test]
+    fn test_transformed_circles() {
+        // rotating and uniformly scaling a circle keeps it a circle
+        let tikz = convert(r#"<svg><circle cx="1" r="2" transform="rotate(90) scale(2)"/></svg>"#);
+        match tikz.draws()[0].path_sections[..] {
+            [PathSection::Circle(Point(x, y), r)] => {
+                assert!(x.abs() < 1e-5 && (y - 2.0).abs() < 1e-5 && r == 4.0)
+            }
+            ref other => panic!("expected a circle, got {:?}", other),
+        }
+
+        // a non-uniform scale along the axes gives an ellipse
+        let tikz = convert(r#"<svg><circle r="2" transform="scale(2,1)"/></svg>"#);
+        assert_eq!(
+            tikz.draws()[0].path_sections,
+            vec![PathSection::Ellipse(Point(0.0, 0.0), 4.0, 2.0)]
+        );
+
+        // and a skewed one can only be traced
+        let tikz = convert(r#"<svg><circle r="2" transform="skewX(30)"/></svg>"#);
+        let sections = &tikz.draws()[0].path_sections;
+        assert_eq!(sections[0], PathSection::Move(Point(2.0, 0.0)));
+        assert!(matches!(sections[1], PathSection::Curve(..)));
+        assert_eq!(sections.last(), Some(&PathSection::Cycle));
+    }
+
+    #[test]
+    fn test_line_polyline_and_polygon() {
+        let tikz = convert(
+            r#"<svg><line x1="1" y1="2" x2="3" y2="4"/>
+            <polyline points="0,0 10,0,10 10 20-5"/>
+            <polygon points="0 0 1 0 1 1" transform="translate(1)"/>
+            <polygon points=""/></svg>"#,
+        );
+        let draws = tikz.draws();
+        assert_eq!(draws.len(), 3);
+        assert_eq!(
+            draws[0].path_sections,
+            vec![
+                PathSection::Move(Point(1.0, 2.0)),
+                PathSection::Line(Point(3.0, 4.0)),
+            ]
+        );
+        assert_eq!(
+            draws[1].path_sections,
+            vec![
+                PathSection::Move(Point(0.0, 0.0)),
+                PathSection::Line(Point(10.0, 0.0)),
+                PathSection::Line(Point(10.0, 10.0)),
+                PathSection::Line(Point(20.0, -5.0)),
+            ]
+        );
+        assert_eq!(
+            draws[2].path_sections,
+            vec![
+                PathSection::Move(Point(1.0, 0.0)),
+                PathSection::Line(Point(2.0, 0.0)),
+                PathSection::Line(Point(2.0, 1.0)),
+                PathSection::Cycle,
+            ]
+        );
+
+        let svg = r#"<svg><polyline points="0,0 10,0 10"/></svg>"#;
+        assert!(parse_svg_with(svg.as_bytes(), &Options::default()).is_err());
+    }
+
+    #[test]
+    fn test_rotated_rect_becomes_a_path() {
+        let tikz = convert(r#"<svg><rect width="2" height="1" transform="rotate(90)"/></svg>"#);
+        let sections = &tikz.draws()[0].path_sections;
+        assert_eq!(sections.len(), 5);
+        assert!(matches!(sections[0], PathSection::Move(_)));
+    }
+}
@@ -0,0 +1,323 @@
+//! Presentation properties (fill, stroke, ...) and how they are inherited
+
+use crate::color::{Color, ColorTable, Paint};
+use crate::dom::Element;
+use crate::viewport::Viewport;
+use crate::{Attribute, ConversionError};
+
+/// The presentation properties that are understood
+pub(crate) const PROPERTIES: &[&str] = &[
+    "fill",
+    "fill-rule",
+    "stroke",
+    "stroke-width",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "fill-opacity",
+    "stroke-opacity",
+    "opacity",
+    "color",
+    // not part of the style, but listed so that stylesheets can set them on
+    // gradient stops
+    "stop-color",
+    "stop-opacity",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// The presentation properties of an element. As parsed from the element,
+/// only the properties it specifies itself are set. Once it has inherited
+/// from its parent, every inherited property is set, giving its computed
+/// style.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Style {
+    pub fill: Option<Paint>,
+    pub fill_rule: Option<FillRule>,
+    pub stroke: Option<Paint>,
+    /// Lengths are in the user space of the element they're specified on
+    pub stroke_width: Option<f32>,
+    pub stroke_linecap: Option<LineCap>,
+    pub stroke_linejoin: Option<LineJoin>,
+    pub stroke_miterlimit: Option<f32>,
+    /// Alternating dash and gap lengths, with an even number of entries;
+    /// empty for a solid line
+    pub stroke_dasharray: Option<Vec<f32>>,
+    pub stroke_dashoffset: Option<f32>,
+    pub fill_opacity: Option<f32>,
+    pub stroke_opacity: Option<f32>,
+    /// The opacity of the element as a whole. Unlike the rest, this isn't
+    /// inherited, so it only ever comes from the element itself.
+    pub opacity: Option<f32>,
+    /// The colour `currentColor` stands for, with its alpha
+    pub color: Option<(Color, f32)>,
+}
+
+impl Style {
+    /// The initial values of the inherited properties, as they apply to the
+    /// root element
+    pub fn initial() -> Self {
+        Style {
+            fill: Some(Paint::Color(Color(0, 0, 0), 1.0)),
+            fill_rule: Some(FillRule::NonZero),
+            stroke: Some(Paint::None),
+            stroke_width: Some(1.0),
+            stroke_linecap: Some(LineCap::Butt),
+            stroke_linejoin: Some(LineJoin::Miter),
+            stroke_miterlimit: Some(4.0),
+            stroke_dasharray: Some(vec![]),
+            stroke_dashoffset: Some(0.0),
+            fill_opacity: Some(1.0),
+            stroke_opacity: Some(1.0),
+            opacity: None,
+            color: Some((Color(0, 0, 0), 1.0)),
+        }
+    }
+
+    /// The properties `element` specifies through presentation attributes,
+    /// with percentages of the `viewport`
+    pub fn specified(element: &Element, viewport: &Viewport) -> Result<Self, ConversionError> {
+        let mut style = Style::default();
+        for &property in PROPERTIES {
+            if let Some(value) = element.attr(property) {
+                style
+                    .set(property, value.trim(), viewport)
+                    .ok_or_else(|| element.malformed(property))?;
+            }
+        }
+        Ok(style)
+    }
+
+    /// Sets `property` from its SVG value, giving `None` if the value can't
+    /// be understood. `inherit` leaves the property unset.
+    pub fn set(&mut self, property: &str, value: &str, viewport: &Viewport) -> Option<()> {
+        if value == "inherit" {
+            return Some(());
+        }
+        match property {
+            "fill" => self.fill = Some(Paint::parse(value)?),
+            "stroke" => self.stroke = Some(Paint::parse(value)?),
+            "stroke-width" => {
+                self.stroke_width = Some(viewport.length(property, value).filter(|&w| w >= 0.0)?)
+            }
+            "stroke-linecap" => {
+                self.stroke_linecap = Some(match value {
+                    "butt" => LineCap::Butt,
+                    "round" => LineCap::Round,
+                    "square" => LineCap::Square,
+                    _ => return None,
+                })
+            }
+            "stroke-linejoin" => {
+                self.stroke_linejoin = Some(match value {
+                    // SVG 2's `arcs` falls back to `miter` where it isn't
+                    // supported, and tikz has no `miter-clip`
+                    "miter" | "miter-clip" | "arcs" => LineJoin::Miter,
+                    "round" => LineJoin::Round,
+                    "bevel" => LineJoin::Bevel,
+                    _ => return None,
+                })
+            }
+            "stroke-miterlimit" => {
+                self.stroke_miterlimit = Some(value.parse().ok().filter(|&m: &f32| m >= 1.0)?)
+            }
+            "stroke-dasharray" => self.stroke_dasharray = Some(parse_dasharray(value, viewport)?),
+            "stroke-dashoffset" => self.stroke_dashoffset = Some(viewport.length(property, value)?),
+            "fill-opacity" => self.fill_opacity = Some(parse_opacity(value)?),
+            "stroke-opacity" => self.stroke_opacity = Some(parse_opacity(value)?),
+            "opacity" => self.opacity = Some(parse_opacity(value)?),
+            "color" => self.color = Some(Color::parse(value)?),
+            "fill-rule" => {
+                self.fill_rule = Some(match value {
+                    "nonzero" => FillRule::NonZero,
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => return None,
+                })
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    /// Fills in the properties this style doesn't specify from the computed
+    /// style of the parent element
+    pub fn inherit(self, parent: &Style) -> Style {
+        Style {
+            fill: self.fill.or_else(|| parent.fill.clone()),
+            fill_rule: self.fill_rule.or(parent.fill_rule),
+            stroke: self.stroke.or_else(|| parent.stroke.clone()),
+            stroke_width: self.stroke_width.or(parent.stroke_width),
+            stroke_linecap: self.stroke_linecap.or(parent.stroke_linecap),
+            stroke_linejoin: self.stroke_linejoin.or(parent.stroke_linejoin),
+            stroke_miterlimit: self.stroke_miterlimit.or(parent.stroke_miterlimit),
+            stroke_dasharray: self
+                .stroke_dasharray
+                .or_else(|| parent.stroke_dasharray.clone()),
+            stroke_dashoffset: self.stroke_dashoffset.or(parent.stroke_dashoffset),
+            fill_opacity: self.fill_opacity.or(parent.fill_opacity),
+            stroke_opacity: self.stroke_opacity.or(parent.stroke_opacity),
+            opacity: self.opacity,
+            color: self.color.or(parent.color),
+        }
+    }
+
+    /// The tikz attributes for the properties that are set, declaring the
+    /// colours they use in `colors`. Lengths are multiplied by `scale` to
+    /// bring them into tikz units.
+    pub fn attributes(&self, scale: f32, colors: &mut ColorTable) -> Vec<Attribute> {
+        let mut attributes = Vec::new();
+        let opacity = self.opacity.unwrap_or(1.0);
+        if opacity < 1.0 {
+            attributes.push(Attribute::param("opacity", format!("{:.4}", opacity)));
+        }
+        if let Some(fill) = &self.fill {
+            let fill_opacity = self.fill_opacity.unwrap_or(1.0);
+            self.paint_attributes(fill, fill_opacity, "fill", colors, &mut attributes);
+        }
+        match self.fill_rule {
+            Some(FillRule::NonZero) => attributes.push(Attribute::setting("nonzero rule")),
+            Some(FillRule::EvenOdd) => attributes.push(Attribute::setting("even odd rule")),
+            None => {}
+        }
+        if let Some(stroke) = &self.stroke {
+            let stroke_opacity = self.stroke_opacity.unwrap_or(1.0);
+            self.paint_attributes(stroke, stroke_opacity, "draw", colors, &mut attributes);
+        }
+        // the rest of the stroke properties don't matter without a stroke
+        if self.stroke != Some(Paint::None) {
+            self.stroke_attributes(scale, &mut attributes);
+        }
+        attributes
+    }
+
+    fn stroke_attributes(&self, scale: f32, attributes: &mut Vec<Attribute>) {
+        let length = |l: f32| format!("{:.4}cm", l * scale);
+        if let Some(width) = self.stroke_width {
+            attributes.push(Attribute::param("line width", length(width)));
+        }
+        match self.stroke_linecap {
+            Some(LineCap::Butt) => attributes.push(Attribute::param("line cap", "butt")),
+            Some(LineCap::Round) => attributes.push(Attribute::param("line cap", "round")),
+            Some(LineCap::Square) => attributes.push(Attribute::param("line cap", "rect")),
+            None => {}
+        }
+        match self.stroke_linejoin {
+            Some(LineJoin::Miter) => attributes.push(Attribute::param("line join", "miter")),
+            Some(LineJoin::Round) => attributes.push(Attribute::param("line join", "round")),
+            Some(LineJoin::Bevel) => attributes.push(Attribute::param("line join", "bevel")),
+            None => {}
+        }
+        if let Some(limit) = self.stroke_miterlimit {
+            attributes.push(Attribute::param("miter limit", format!("{:.4}", limit)));
+        }
+        match self.stroke_dasharray.as_deref() {
+            Some([]) => attributes.push(Attribute::setting("solid")),
+            Some(dashes) => {
+                let pattern: Vec<String> = dashes
+                    .chunks(2)
+                    .map(|dash| format!("on {} off {}", length(dash[0]), length(dash[1])))
+                    .collect();
+                attributes.push(Attribute::param("dash pattern", pattern.join(" ")));
+                if let Some(offset) = self.stroke_dashoffset {
+                    attributes.push(Attribute::param("dash phase", length(offset)));
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// The colour and opacity of a fill or stroke (`key` being `fill` or
+    /// `draw`), where the paint's own alpha is multiplied by `opacity`
+    fn paint_attributes(
+        &self,
+        paint: &Paint,
+        opacity: f32,
+        key: &str,
+        colors: &mut ColorTable,
+        attributes: &mut Vec<Attribute>,
+    ) {
+        let current = *paint == Paint::CurrentColor;
+        let (color, alpha) = match paint {
+            Paint::None => return attributes.push(Attribute::param(key, "none")),
+            Paint::Color(color, alpha) => (*color, *alpha),
+            Paint::CurrentColor => self.color.unwrap_or((Color(0, 0, 0), 1.0)),
+            // paint servers are resolved by the converter, which only leaves
+            // those it can't draw
+            Paint::Url(_, fallback) => {
+                return self.paint_attributes(fallback, opacity, key, colors, attributes)
+            }
+        };
+        match colors.key(color, current) {
+            // the style keys set the fill by default
+            Some(style) if key == "fill" => attributes.push(Attribute::setting(style)),
+            Some(style) => attributes.push(Attribute::param(style, key)),
+            None => attributes.push(Attribute::param(key, colors.name(color))),
+        }
+        let alpha = alpha * opacity;
+        if alpha < 1.0 {
+            // `fill opacity` and `draw opacity` replace, rather than
+            // multiply, any `opacity` set before them
+            let alpha = alpha * self.opacity.unwrap_or(1.0);
+            attributes.push(Attribute::param(
+                format!("{} opacity", key),
+                format!("{:.4}", alpha),
+            ));
+        }
+    }
+}
+
+/// Parses an opacity given as a number or a percentage, clamped into the
+/// range 0 to 1
+fn parse_opacity(value: &str) -> Option<f32> {
+    let opacity = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+    Some(opacity.clamp(0.0, 1.0))
+}
+
+/// Parses a `stroke-dasharray`, repeating a list of odd length to make it
+/// even as the spec requires. `none`, or dashes that are all zero, give a
+/// solid line. Percentages are of the `viewport`.
+fn parse_dasharray(value: &str, viewport: &Viewport) -> Option<Vec<f32>> {
+    if value == "none" {
+        return Some(vec![]);
+    }
+    let dashes = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|dash| !dash.is_empty())
+        .map(|dash| {
+            viewport
+                .length("stroke-dasharray", dash)
+                .filter(|&l| l >= 0.0)
+        })
+        .collect::<Option<Vec<f32>>>()?;
+    if dashes.iter().all(|&dash| dash == 0.0) {
+        return Some(vec![]);
+    }
+    Some(if dashes.len() % 2 == 1 {
+        dashes.repeat(2)
+    } else {
+        dashes
+    })
+}
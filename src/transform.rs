@@ -0,0 +1,211 @@
+use crate::{parse_numbers, Attribute, PathSection, Point};
+
+/// An affine transformation, in the same form as the SVG `matrix(a,b,c,d,e,f)`
+/// transform:
+///
+/// ```text
+/// | a c e |
+/// | b d f |
+/// | 0 0 1 |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform::matrix(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn matrix(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Transform { a, b, c, d, e, f }
+    }
+
+    pub fn translate(tx: f32, ty: f32) -> Self {
+        Transform::matrix(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Transform::matrix(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// A rotation by `angle` degrees, clockwise in SVG's y-down space
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Transform::matrix(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Parses the value of an SVG `transform` attribute, e.g.
+    /// `translate(10,5) rotate(45 24 24)`. The listed transformations apply
+    /// right to left, as if each were on a nested group.
+    pub fn parse(s: &str) -> Option<Self> {
+        let separator = |c: char| c.is_whitespace() || c == ',';
+        let mut result = Transform::identity();
+        let mut rest = s.trim_start_matches(separator);
+        while !rest.is_empty() {
+            let (name, tail) = rest.split_once('(')?;
+            let (args, tail) = tail.split_once(')')?;
+            let t = match (name.trim(), parse_numbers(args)?.as_slice()) {
+                ("matrix", &[a, b, c, d, e, f]) => Transform::matrix(a, b, c, d, e, f),
+                ("translate", &[tx]) => Transform::translate(tx, 0.0),
+                ("translate", &[tx, ty]) => Transform::translate(tx, ty),
+                ("scale", &[s]) => Transform::scale(s, s),
+                ("scale", &[sx, sy]) => Transform::scale(sx, sy),
+                ("rotate", &[angle]) => Transform::rotate(angle),
+                ("rotate", &[angle, cx, cy]) => Transform::translate(cx, cy)
+                    .compose(&Transform::rotate(angle))
+                    .compose(&Transform::translate(-cx, -cy)),
+                ("skewX", &[angle]) => {
+                    Transform::matrix(1.0, 0.0, angle.to_radians().tan(), 1.0, 0.0, 0.0)
+                }
+                ("skewY", &[angle]) => {
+                    Transform::matrix(1.0, angle.to_radians().tan(), 0.0, 1.0, 0.0, 0.0)
+                }
+                _ => return None,
+            };
+            result = result.compose(&t);
+            rest = tail.trim_start_matches(separator);
+        }
+        Some(result)
+    }
+
+    /// The tikz `cm` option that applies this transformation
+    pub fn to_attribute(&self) -> Attribute {
+        Attribute::param(
+            "cm",
+            format!(
+                "{{{:.4},{:.4},{:.4},{:.4},{}}}",
+                self.a,
+                self.b,
+                self.c,
+                self.d,
+                Point(self.e, self.f)
+            ),
+        )
+    }
+
+    /// The transformation that applies `inner` first, and then `self`
+    pub fn compose(&self, inner: &Transform) -> Transform {
+        Transform {
+            a: self.a * inner.a + self.c * inner.b,
+            b: self.b * inner.a + self.d * inner.b,
+            c: self.a * inner.c + self.c * inner.d,
+            d: self.b * inner.c + self.d * inner.d,
+            e: self.a * inner.e + self.c * inner.f + self.e,
+            f: self.b * inner.e + self.d * inner.f + self.f,
+        }
+    }
+
+    /// Whether horizontal and vertical lines stay horizontal and vertical
+    pub fn is_axis_aligned(&self) -> bool {
+        self.b == 0.0 && self.c == 0.0
+    }
+
+    /// The scale factor, if this transformation scales equally in every
+    /// direction (it is made up of translations, rotations, reflections
+    /// and uniform scaling only)
+    pub fn uniform_scale(&self) -> Option<f32> {
+        let close = |x: f32, y: f32| (x - y).abs() <= 1e-6 * x.abs().max(y.abs()).max(1.0);
+        let conformal = (close(self.a, self.d) && close(self.b, -self.c))
+            || (close(self.a, -self.d) && close(self.b, self.c));
+        conformal.then(|| (self.a * self.d - self.b * self.c).abs().sqrt())
+    }
+
+    /// The factor lengths are scaled by: exact for uniform scaling, and the
+    /// geometric mean of the two axes' scale factors otherwise
+    pub fn mean_scale(&self) -> f32 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+
+    pub fn apply(&self, p: Point) -> Point {
+        Point(
+            self.a * p.0 + self.c * p.1 + self.e,
+            self.b * p.0 + self.d * p.1 + self.f,
+        )
+    }
+}
+
+impl PathSection {
+    pub fn transformed(&self, t: &Transform) -> PathSection {
+        match self {
+            PathSection::Move(p) => PathSection::Move(t.apply(*p)),
+            PathSection::Line(p) => PathSection::Line(t.apply(*p)),
+            PathSection::Curve(c1, c2, p) => {
+                PathSection::Curve(t.apply(*c1), t.apply(*c2), t.apply(*p))
+            }
+            PathSection::Cycle => PathSection::Cycle,
+            // only correct for transformations that keep the axes aligned,
+            // see `Transform::is_axis_aligned`; the shapes are traced with
+            // curves rather than transformed otherwise
+            PathSection::Rectangle(p1, p2) => {
+                debug_assert!(t.is_axis_aligned());
+                PathSection::Rectangle(t.apply(*p1), t.apply(*p2))
+            }
+            PathSection::Ellipse(c, rx, ry) => {
+                debug_assert!(t.is_axis_aligned());
+                PathSection::Ellipse(t.apply(*c), rx * t.a.abs(), ry * t.d.abs())
+            }
+            // likewise only for transformations that keep circles circular,
+            // see `Transform::uniform_scale`
+            PathSection::Circle(c, r) => {
+                debug_assert!(t.uniform_scale().is_some());
+                PathSection::Circle(t.apply(*c), r * t.uniform_scale().unwrap_or(1.0))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Point, b: Point) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_parse_transform_list() {
+        let t = Transform::parse("translate(10, 5) scale(2)").unwrap();
+        assert_close(t.apply(Point(1.0, 1.0)), Point(12.0, 7.0));
+
+        let t = Transform::parse("rotate(90 24 24)").unwrap();
+        assert_close(t.apply(Point(24.0, 0.0)), Point(48.0, 24.0));
+
+        let t = Transform::parse(" matrix(1 0 0 1 3 4),skewX(45) ").unwrap();
+        assert_close(t.apply(Point(0.0, 2.0)), Point(5.0, 6.0));
+
+        let t = Transform::parse("skewY(45)").unwrap();
+        assert_close(t.apply(Point(2.0, 0.0)), Point(2.0, 2.0));
+
+        assert_eq!(Transform::parse(""), Some(Transform::identity()));
+        assert_eq!(Transform::parse("rotate(1 2)"), None);
+        assert_eq!(Transform::parse("translate(1"), None);
+        assert_eq!(Transform::parse("shear(1)"), None);
+    }
+
+    #[test]
+    fn test_cm_attribute() {
+        let t = Transform::matrix(1.0, 0.0, 0.0, -1.0, 0.0, 48.0);
+        assert_eq!(
+            t.to_attribute().to_string(),
+            "cm={1.0000,0.0000,0.0000,-1.0000,(0.0000, 48.0000)}"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! The Light and Dark variants AWS publishes each icon in, and turning one
+//! into the other. Light icons draw their glyph in the colour of the
+//! icon's category, for light backgrounds; Dark ones draw it in white.
+
+use crate::color::Color;
+use crate::dom::Element;
+use crate::palette;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Light,
+    Dark,
+}
+
+const WHITE: Color = Color(0xFF, 0xFF, 0xFF);
+const SQUID_INK: Color = Color(0x23, 0x2F, 0x3E);
+
+impl Variant {
+    /// Detects the variant from an icon's title or file name, which AWS end
+    /// in `_Light` or `_Dark`
+    pub fn detect(name: &str) -> Option<Variant> {
+        let name = name.trim();
+        let stem = match name.rsplit_once('.') {
+            Some((stem, extension)) if extension.eq_ignore_ascii_case("svg") => stem,
+            _ => name,
+        };
+        let (_, suffix) = stem.rsplit_once(['_', '-'])?;
+        match suffix.to_ascii_lowercase().as_str() {
+            "light" => Some(Variant::Light),
+            "dark" => Some(Variant::Dark),
+            _ => None,
+        }
+    }
+
+    pub fn opposite(self) -> Variant {
+        match self {
+            Variant::Light => Variant::Dark,
+            Variant::Dark => Variant::Light,
+        }
+    }
+
+    /// The colours to replace to turn an icon of the `source` variant with
+    /// the given `title` into this variant, each with its replacement
+    pub(crate) fn remap(self, source: Variant, title: Option<&str>) -> Vec<(Color, Color)> {
+        if source == self {
+            return vec![];
+        }
+        let foreground = title
+            .and_then(palette::category)
+            .and_then(palette::foreground);
+        match (self, foreground) {
+            (Variant::Dark, _) => palette::foregrounds().map(|color| (color, WHITE)).collect(),
+            (Variant::Light, Some(color)) => vec![(WHITE, color)],
+            // without a category, the general icons' colour will do
+            (Variant::Light, None) => vec![(WHITE, SQUID_INK)],
+        }
+    }
+}
+
+/// The text of the root element's `<title>`, if it has one
+pub(crate) fn title<'e>(root: &'e Element) -> Option<&'e str> {
+    root.children
+        .iter()
+        .find(|child| child.name == "title")
+        .map(|title| title.text.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_svg, parse_svg_with, Options};
+    use std::fs::File;
+
+    #[test]
+    fn test_detect_variant() {
+        assert_eq!(
+            Variant::detect("Icon-Resource/Compute/Res_Amazon-Lambda_Lambda-Function_48_Light"),
+            Some(Variant::Light)
+        );
+        assert_eq!(
+            Variant::detect("icons/Res_Amazon-Lambda_Lambda-Function_48_Dark.svg"),
+            Some(Variant::Dark)
+        );
+        assert_eq!(Variant::detect("Arch_AWS-Lambda_48.svg"), None);
+        assert_eq!(Variant::detect("Light"), None);
+    }
+
+    #[test]
+    fn test_swap_variant() -> anyhow::Result<()> {
+        let tikz = parse_svg(File::open("testfiles/lambda.svg")?)?;
+        assert_eq!(tikz.variant(), Some(Variant::Light));
+
+        let options = Options {
+            variant: Some(Variant::Dark),
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(File::open("testfiles/lambda.svg")?, &options)?;
+        assert_eq!(tikz.variant(), Some(Variant::Dark));
+        assert!(tikz.to_string().contains("\\draw[fill=svgFFFFFF,"));
+        assert!(!tikz.to_string().contains("D45B07"));
+
+        // and back again, finding the colour from the category
+        let svg = r##"<svg><title>Icon-Resource/Compute/Res_Amazon-EC2_48_Dark</title>
+            <path d="M0,0" fill="#FFFFFF"/></svg>"##;
+        let options = Options {
+            variant: Some(Variant::Light),
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert!(tikz.to_string().contains("\\draw[fill=svgD45B07,"));
+
+        // an icon that already is the variant asked for is left alone
+        let options = Options {
+            variant: Some(Variant::Dark),
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert!(tikz.to_string().contains("\\draw[fill=svgFFFFFF,"));
+
+        // without a title, the variant has to be given to be remapped
+        let svg = r##"<svg><path d="M0,0" fill="#FFFFFF"/></svg>"##;
+        let options = Options {
+            variant: Some(Variant::Light),
+            ..Options::default()
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert!(tikz.to_string().contains("\\draw[fill=svgFFFFFF,"));
+        let options = Options {
+            source_variant: Variant::detect("Res_Amazon-EC2_48_Dark.svg"),
+            ..options
+        };
+        let tikz = parse_svg_with(svg.as_bytes(), &options)?;
+        assert!(tikz.to_string().contains("\\draw[fill=svg232F3E,"));
+
+        Ok(())
+    }
+}
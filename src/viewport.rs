@@ -0,0 +1,399 @@
+//! Mapping of the root `<svg>` element's user space into tikz coordinates,
+//! and the viewports percentage lengths refer to
+
+use crate::dom::Element;
+use crate::{parse_length, parse_numbers, ConversionError, Options, Transform};
+
+/// The size of the viewport an element's percentage lengths refer to, in
+/// the user units of the element that establishes it
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Viewport {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    /// Parses the value of `property` into user units, resolving a
+    /// percentage against the viewport's width for horizontal lengths, its
+    /// height for vertical ones and its normalised diagonal for the rest
+    pub fn length(&self, property: &str, value: &str) -> Option<f32> {
+        let percent = match value.trim().strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => return parse_length(value),
+        };
+        let reference = match property {
+            "x" | "cx" | "x1" | "x2" | "rx" | "width" => self.width,
+            "y" | "cy" | "y1" | "y2" | "ry" | "height" => self.height,
+            _ => ((self.width.powi(2) + self.height.powi(2)) / 2.0).sqrt(),
+        };
+        Some(percent * reference)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Min,
+    Mid,
+    Max,
+}
+
+impl Align {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Min" => Some(Align::Min),
+            "Mid" => Some(Align::Mid),
+            "Max" => Some(Align::Max),
+            _ => None,
+        }
+    }
+
+    /// How far to shift the viewBox given the `free` space left over
+    fn offset(self, free: f32) -> f32 {
+        match self {
+            Align::Min => 0.0,
+            Align::Mid => free / 2.0,
+            Align::Max => free,
+        }
+    }
+}
+
+/// How the viewBox is fitted into a viewport of a different aspect ratio,
+/// as given by `preserveAspectRatio`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AspectRatio {
+    /// x and y alignment, or `None` to stretch the viewBox non-uniformly
+    align: Option<(Align, Align)>,
+    /// Scale the viewBox to cover the viewport, rather than to fit in it
+    slice: bool,
+}
+
+impl Default for AspectRatio {
+    fn default() -> Self {
+        AspectRatio {
+            align: Some((Align::Mid, Align::Mid)),
+            slice: false,
+        }
+    }
+}
+
+impl AspectRatio {
+    fn parse(s: &str) -> Option<Self> {
+        let mut words = s.split_whitespace().peekable();
+        // `defer` only matters for <image>, so it can be skipped
+        words.next_if_eq(&"defer");
+        let align = match words.next()? {
+            "none" => None,
+            align => {
+                if align.get(0..1)? != "x" || align.get(4..5)? != "Y" {
+                    return None;
+                }
+                Some((
+                    Align::parse(align.get(1..4)?)?,
+                    Align::parse(align.get(5..)?)?,
+                ))
+            }
+        };
+        let slice = match words.next() {
+            None | Some("meet") => false,
+            Some("slice") => true,
+            Some(_) => return None,
+        };
+        match words.next() {
+            None => Some(AspectRatio { align, slice }),
+            Some(_) => None,
+        }
+    }
+
+    /// The transformation from the viewBox `(x, y, width, height)` into a
+    /// viewport of the given size, with its origin at the top left. Content
+    /// outside the viewport (with `slice`) is not clipped.
+    fn view_box_transform(
+        &self,
+        view_box: (f32, f32, f32, f32),
+        width: f32,
+        height: f32,
+    ) -> Transform {
+        let (x, y, vb_width, vb_height) = view_box;
+        let origin = Transform::translate(-x, -y);
+        let (sx, sy) = (width / vb_width, height / vb_height);
+        match self.align {
+            None => Transform::scale(sx, sy).compose(&origin),
+            Some((align_x, align_y)) => {
+                let s = if self.slice { sx.max(sy) } else { sx.min(sy) };
+                Transform::translate(
+                    align_x.offset(width - vb_width * s),
+                    align_y.offset(height - vb_height * s),
+                )
+                .compose(&Transform::scale(s, s))
+                .compose(&origin)
+            }
+        }
+    }
+}
+
+/// The `viewBox` of `element` as `(x, y, width, height)`, if it has one
+fn view_box(element: &Element) -> Result<Option<(f32, f32, f32, f32)>, ConversionError> {
+    match element.attr("viewBox") {
+        Some(value) => match parse_numbers(value).as_deref() {
+            Some(&[x, y, width, height]) if width > 0.0 && height > 0.0 => {
+                Ok(Some((x, y, width, height)))
+            }
+            _ => Err(element.malformed("viewBox")),
+        },
+        None => Ok(None),
+    }
+}
+
+fn aspect_ratio(element: &Element) -> Result<AspectRatio, ConversionError> {
+    match element.attr("preserveAspectRatio") {
+        Some(value) => {
+            AspectRatio::parse(value).ok_or_else(|| element.malformed("preserveAspectRatio"))
+        }
+        None => Ok(AspectRatio::default()),
+    }
+}
+
+/// The transformation from the user space of a `<symbol>` to that of the
+/// `<use>` instantiating it with the given `width` and `height`, which
+/// default to the symbol's own and then to its viewBox size, along with the
+/// viewport the symbol establishes. `parent` is the viewport of the `<use>`.
+pub(crate) fn symbol_transform(
+    symbol: &Element,
+    width: Option<f32>,
+    height: Option<f32>,
+    parent: &Viewport,
+) -> Result<(Transform, Viewport), ConversionError> {
+    let width = width.or(symbol.length("width", parent)?);
+    let height = height.or(symbol.length("height", parent)?);
+    let view_box = match view_box(symbol)? {
+        Some(view_box) => view_box,
+        None => {
+            let viewport = Viewport {
+                width: width.unwrap_or(parent.width),
+                height: height.unwrap_or(parent.height),
+            };
+            return Ok((Transform::identity(), viewport));
+        }
+    };
+    let transform = aspect_ratio(symbol)?.view_box_transform(
+        view_box,
+        width.unwrap_or(view_box.2),
+        height.unwrap_or(view_box.3),
+    );
+    let viewport = Viewport {
+        width: view_box.2,
+        height: view_box.3,
+    };
+    Ok((transform, viewport))
+}
+
+/// The transformation from the user space of a nested `<svg>` element to
+/// that of its parent, placing it at its `x` and `y` and fitting its
+/// viewBox into its `width` and `height` (100% by default), along with the
+/// viewport it establishes. `parent` is the viewport of its parent. Like
+/// with `slice`, content outside the viewport is not clipped.
+pub(crate) fn nested_svg_transform(
+    element: &Element,
+    parent: &Viewport,
+) -> Result<(Transform, Viewport), ConversionError> {
+    let x = element.length("x", parent)?.unwrap_or(0.0);
+    let y = element.length("y", parent)?.unwrap_or(0.0);
+    let width = element.length("width", parent)?.unwrap_or(parent.width);
+    let height = element.length("height", parent)?.unwrap_or(parent.height);
+    let (fit, viewport) = symbol_transform(element, Some(width), Some(height), parent)?;
+    Ok((Transform::translate(x, y).compose(&fit), viewport))
+}
+
+/// The viewport of the root `<svg>` element: its viewBox, or else its size
+pub(crate) fn root_viewport(root: &Element) -> Result<Viewport, ConversionError> {
+    Ok(match view_box(root)? {
+        Some((_, _, width, height)) => Viewport { width, height },
+        // relative sizes can't be resolved without a containing document,
+        // and leave nothing to be a percentage of
+        None => Viewport {
+            width: root.attr("width").and_then(parse_length).unwrap_or(0.0),
+            height: root.attr("height").and_then(parse_length).unwrap_or(0.0),
+        },
+    })
+}
+
+/// The transformation from the user space of the root `<svg>` element to
+/// tikz coordinates
+pub(crate) fn root_transform(
+    root: &Element,
+    options: &Options,
+) -> Result<Transform, ConversionError> {
+    let view_box = view_box(root)?;
+    let aspect_ratio = aspect_ratio(root)?;
+    // relative lengths can't be resolved without a containing document, so
+    // fall back to the viewBox size as if they were 100%
+    let width = root.attr("width").and_then(parse_length);
+    let width = width.or(view_box.map(|v| v.2)).unwrap_or(0.0);
+    let height = root.attr("height").and_then(parse_length);
+    let height = height.or(view_box.map(|v| v.3)).unwrap_or(0.0);
+
+    if !options.map_viewport {
+        if !options.flip_y {
+            return Ok(Transform::identity());
+        }
+        // mirror about the horizontal centre line of the viewBox, so it
+        // maps onto itself
+        let (top, height) = view_box.map_or((0.0, height), |v| (v.1, v.3));
+        return Ok(Transform::matrix(
+            1.0,
+            0.0,
+            0.0,
+            -1.0,
+            0.0,
+            2.0 * top + height,
+        ));
+    }
+
+    let mut transform = match view_box {
+        Some(view_box) => aspect_ratio.view_box_transform(view_box, width, height),
+        None => Transform::identity(),
+    };
+    if options.flip_y {
+        // put the origin at the bottom left of the viewport
+        transform = Transform::matrix(1.0, 0.0, 0.0, -1.0, 0.0, height).compose(&transform);
+    }
+    if let Some(size) = options.scale_to {
+        let longest = width.max(height);
+        if longest > 0.0 {
+            transform = Transform::scale(size / longest, size / longest).compose(&transform);
+        }
+    }
+    Ok(transform)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_svg, parse_svg_with, Options, PathSection, Point};
+
+    fn first_point(svg: &str, options: &Options) -> Point {
+        let tikz = parse_svg_with(svg.as_bytes(), options).unwrap();
+        match tikz.draws()[0].path_sections[0] {
+            PathSection::Move(p) => p,
+            _ => panic!("path doesn't start with a move"),
+        }
+    }
+
+    #[test]
+    fn test_aspect_ratio_parsing() {
+        use super::{Align, AspectRatio};
+        assert_eq!(AspectRatio::parse("xMidYMid"), Some(AspectRatio::default()));
+        assert_eq!(
+            AspectRatio::parse("defer xMinYMax slice"),
+            Some(AspectRatio {
+                align: Some((Align::Min, Align::Max)),
+                slice: true
+            })
+        );
+        assert_eq!(
+            AspectRatio::parse("none"),
+            Some(AspectRatio {
+                align: None,
+                slice: false
+            })
+        );
+        assert_eq!(AspectRatio::parse("xMidYmid"), None);
+        assert_eq!(AspectRatio::parse("xMinYMin meet extra"), None);
+    }
+
+    #[test]
+    fn test_view_box_is_fitted_to_viewport() {
+        let options = Options::default();
+        // 20x10 viewBox in a 40x40 viewport: scaled by 2 and centred
+        // vertically, leaving a 10 unit margin top and bottom
+        let svg = r#"<svg viewBox="10 10 20 10" width="40" height="40px">
+            <path d="M10,10 L30,20"/></svg>"#;
+        assert_eq!(first_point(svg, &options), Point(0.0, 30.0));
+
+        let svg = r#"<svg viewBox="10 10 20 10" width="40" height="40"
+            preserveAspectRatio="xMaxYMax slice"><path d="M10,10"/></svg>"#;
+        // scaled by 4, overflowing the viewport to the left
+        assert_eq!(first_point(svg, &options), Point(-40.0, 40.0));
+
+        let svg = r#"<svg viewBox="10 10 20 10" width="40" height="40"
+            preserveAspectRatio="none"><path d="M30,20"/></svg>"#;
+        assert_eq!(first_point(svg, &options), Point(40.0, 0.0));
+
+        let svg = r#"<svg viewBox="0 0 48 48" width="0.5in" height="0.5in">
+            <path d="M48,0"/></svg>"#;
+        assert_eq!(first_point(svg, &options), Point(48.0, 48.0));
+    }
+
+    #[test]
+    fn test_scale_to_unit_box() {
+        let options = Options {
+            scale_to: Some(1.0),
+            ..Options::default()
+        };
+        let svg = r#"<svg viewBox="0 0 48 48" width="48px" height="48px">
+            <path d="M24,12"/></svg>"#;
+        assert_eq!(first_point(svg, &options), Point(0.5, 0.75));
+    }
+
+    #[test]
+    fn test_raw_coordinates() {
+        let options = Options {
+            flip_y: false,
+            map_viewport: false,
+            ..Options::default()
+        };
+        let svg = r#"<svg viewBox="10 10 20 10" width="40" height="40">
+            <path d="M12,13"/></svg>"#;
+        assert_eq!(first_point(svg, &options), Point(12.0, 13.0));
+    }
+
+    #[test]
+    fn test_percentages_of_the_viewport() {
+        let options = Options {
+            flip_y: false,
+            map_viewport: false,
+            ..Options::default()
+        };
+        let svg = r#"<svg viewBox="0 0 40 30">
+            <rect width="100%" height="50%" stroke="black" stroke-width="1%"
+                stroke-dasharray="10% 1em"/></svg>"#;
+        let tikz = parse_svg_with(svg.as_bytes(), &options).unwrap();
+        assert_eq!(
+            tikz.draws()[0].path_sections,
+            vec![PathSection::Rectangle(Point(0.0, 0.0), Point(40.0, 15.0))]
+        );
+        // the normalised diagonal of a 40x30 viewport is sqrt(1250)
+        let tikz = tikz.to_string();
+        assert!(tikz.contains("line width=0.3536cm"));
+        assert!(tikz.contains("dash pattern=on 3.5355cm off 16.0000cm"));
+    }
+
+    #[test]
+    fn test_nested_svg_is_fitted_to_its_viewport() {
+        let options = Options {
+            flip_y: false,
+            map_viewport: false,
+            ..Options::default()
+        };
+        let svg = r#"<svg viewBox="0 0 40 30">
+            <svg x="5" y="5" width="2" height="2" viewBox="0 0 1 1">
+                <rect width="1" height="1"/></svg>
+            <svg x="10%"><rect width="50%" height="1"/></svg></svg>"#;
+        let tikz = parse_svg_with(svg.as_bytes(), &options).unwrap();
+        let draws = tikz.draws();
+        assert_eq!(
+            draws[0].path_sections,
+            vec![PathSection::Rectangle(Point(5.0, 5.0), Point(7.0, 7.0))]
+        );
+        // without a viewBox, only the origin moves
+        assert_eq!(
+            draws[1].path_sections,
+            vec![PathSection::Rectangle(Point(4.0, 0.0), Point(24.0, 1.0))]
+        );
+    }
+
+    #[test]
+    fn test_malformed_view_box() {
+        let svg = r#"<svg viewBox="0 0 48"><path d="M0,0"/></svg>"#;
+        assert!(parse_svg(svg.as_bytes()).is_err());
+    }
+}